@@ -0,0 +1,74 @@
+#[macro_use]
+extern crate criterion;
+extern crate x264;
+
+use criterion::Criterion;
+use x264::{Colorspace, Encoder, Image, Plane, Preset, Setup, Tune};
+
+const WIDTH: usize = 1280;
+const HEIGHT: usize = 720;
+
+fn build() -> Encoder {
+    Setup::preset(Preset::Ultrafast, Tune::None, false, true)
+        .fps(60, 1)
+        .build(Colorspace::I420, WIDTH as _, HEIGHT as _)
+        .unwrap()
+}
+
+// Compares encoding with and without wrapping the output, which is the
+// overhead this crate adds on top of x264.
+fn encode(c: &mut Criterion) {
+    let y = vec![128; WIDTH * HEIGHT];
+    let uv = vec![128; WIDTH * HEIGHT / 4];
+
+    let image = || {
+        Image::new(
+            Colorspace::I420,
+            WIDTH as _,
+            HEIGHT as _,
+            None,
+            &[
+                Plane {
+                    stride: WIDTH as _,
+                    data: &y,
+                },
+                Plane {
+                    stride: WIDTH as i32 / 2,
+                    data: &uv,
+                },
+                Plane {
+                    stride: WIDTH as i32 / 2,
+                    data: &uv,
+                },
+            ],
+        )
+    };
+
+    let mut group = c.benchmark_group("encode");
+
+    let mut encoder = build();
+    let mut pts = 0;
+    group.bench_function("encode", |b| {
+        b.iter(|| {
+            pts += 1;
+            encoder
+                .encode(pts, image())
+                .unwrap()
+                .map(|(data, _)| data.entirety().len())
+        })
+    });
+
+    let mut encoder = build();
+    let mut pts = 0;
+    group.bench_function("encode_discard", |b| {
+        b.iter(|| {
+            pts += 1;
+            encoder.encode_discard(pts, image()).unwrap()
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, encode);
+criterion_main!(benches);
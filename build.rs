@@ -6,6 +6,9 @@ fn main() {
         .probe("x264")
         .expect("Is x264 installed?");
 
+    println!("cargo:rustc-check-cfg=cfg(yuyv)");
+
+    // YUYV and UYVY input was added in x264 build 149.
     let n: Option<u64> = x264.version.split('.').nth(1).and_then(|n| n.parse().ok());
 
     if let Some(n) = n {
@@ -31,8 +31,9 @@ fn main() {
     for i in 0..300 {
         frame(i as f64 / 300.0, &mut canvas);
         let image = Image::rgb(WIDTH as _, HEIGHT as _, &canvas);
-        let (data, _) = encoder.encode((60 * i) as _, image).unwrap();
-        file.write_all(data.entirety()).unwrap();
+        if let Some((data, _)) = encoder.encode((60 * i) as _, image).unwrap() {
+            file.write_all(data.entirety()).unwrap();
+        }
     }
 
     // Finally, flush any delayed frames.
@@ -0,0 +1,152 @@
+use crate::{gray_to_i420, Colorspace, Data, Encoder, Encoding, Image, Picture, Result};
+
+/// Encodes video with an alpha channel as two streams: one for the color,
+/// and one with the alpha as its luma.
+///
+/// H.264 has no alpha channel, so players that support this (e.g. for
+/// overlays) decode both streams and combine them. Both encoders should have
+/// the same frame type settings (bframes, lookahead, keyframe intervals and
+/// threads), so that they delay frames by the same amount and return their
+/// output together. Even then, x264 may order the frames of each stream
+/// differently, so the pictures should be paired by timestamp.
+pub struct AlphaEncoder {
+    color: Encoder,
+    alpha: Encoder,
+}
+
+/// The output of both streams of an `AlphaEncoder` for one frame.
+///
+/// Either may be `None` if its encoder buffered the frame.
+pub struct AlphaOutput<'a> {
+    /// The output of the color stream.
+    pub color: Option<(Data<'a>, Picture)>,
+    /// The output of the alpha stream.
+    pub alpha: Option<(Data<'a>, Picture)>,
+}
+
+impl AlphaEncoder {
+    /// Combines an encoder for the color with one for the alpha.
+    ///
+    /// The alpha encoder must be built with `Setup::full_range`, so that
+    /// players read the alpha's luma as 0 to 255 rather than scaling it from
+    /// the limited range.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the encoders' sizes differ, or if the alpha encoder doesn't
+    /// take I420 images or doesn't signal full range.
+    pub fn new(color: Encoder, alpha: Encoder) -> Self {
+        assert_eq!(color.width(), alpha.width());
+        assert_eq!(color.height(), alpha.height());
+        assert_eq!(alpha.encoding(), Encoding::from(Colorspace::I420));
+        assert!(
+            alpha.full_range(),
+            "the alpha must be encoded as full range"
+        );
+        Self { color, alpha }
+    }
+
+    /// Feeds a frame to both encoders: the color image, and its alpha as a
+    /// tightly packed plane of 8-bit samples.
+    ///
+    /// The alpha is encoded as full-range luma with neutral chroma (see
+    /// `gray_to_i420`), so its values are kept as they are, and is given the
+    /// same forced frame type as the image, so that keyframes line up.
+    ///
+    /// # Errors
+    ///
+    /// Fails as with `Encoder::encode`. If the alpha encoder fails, the color
+    /// encoder has already been fed the frame.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the image doesn't suit the color encoder, as with
+    /// `Encoder::encode`, or if `alpha` is too short.
+    pub fn encode(&mut self, pts: i64, image: Image, alpha: &[u8]) -> Result<AlphaOutput> {
+        let frame_type = *image.frame_type();
+        let plane = gray_to_i420(alpha, self.alpha.width(), self.alpha.height());
+        let mut alpha_image = plane.image();
+        alpha_image.set_frame_type(frame_type);
+        alpha_image.set_full_range(true);
+
+        let color = self.color.encode(pts, image)?;
+        let alpha = self.alpha.encode(pts, alpha_image)?;
+        Ok(AlphaOutput { color, alpha })
+    }
+
+    /// The encoder for the color.
+    pub fn color(&self) -> &Encoder {
+        &self.color
+    }
+
+    /// The encoder for the alpha.
+    pub fn alpha(&self) -> &Encoder {
+        &self.alpha
+    }
+
+    /// The encoder for the color, e.g. to get its headers.
+    pub fn color_mut(&mut self) -> &mut Encoder {
+        &mut self.color
+    }
+
+    /// The encoder for the alpha, e.g. to get its headers.
+    pub fn alpha_mut(&mut self) -> &mut Encoder {
+        &mut self.alpha
+    }
+
+    /// Splits this back into the color and alpha encoders, e.g. to flush
+    /// them.
+    pub fn into_encoders(self) -> (Encoder, Encoder) {
+        (self.color, self.alpha)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Setup;
+    use alloc::vec::Vec;
+
+    const WIDTH: i32 = 64;
+    const HEIGHT: i32 = 64;
+
+    fn build(setup: Setup) -> Encoder {
+        setup.build(Colorspace::I420, WIDTH, HEIGHT).unwrap()
+    }
+
+    #[test]
+    fn streams_pair_up_by_timestamp() {
+        let color = build(Setup::default());
+        let alpha = build(Setup::default().full_range(true));
+        let mut encoder = AlphaEncoder::new(color, alpha);
+
+        let size = (WIDTH * HEIGHT) as usize;
+        let frame = gray_to_i420(&alloc::vec![128; size], WIDTH, HEIGHT);
+        let alpha = alloc::vec![255; size];
+        let (mut color_pts, mut alpha_pts) = (Vec::new(), Vec::new());
+
+        for pts in 0..10 {
+            let output = encoder.encode(pts, frame.image(), &alpha).unwrap();
+            color_pts.extend(output.color.map(|(_, picture)| picture.pts()));
+            alpha_pts.extend(output.alpha.map(|(_, picture)| picture.pts()));
+        }
+
+        let (color, alpha) = encoder.into_encoders();
+        for (encoder, pts) in [(color, &mut color_pts), (alpha, &mut alpha_pts)] {
+            let mut flush = encoder.flush();
+            while let Some(result) = flush.next() {
+                pts.push(result.unwrap().1.pts());
+            }
+            pts.sort_unstable();
+        }
+
+        assert_eq!(color_pts, (0..10).collect::<Vec<_>>());
+        assert_eq!(alpha_pts, color_pts);
+    }
+
+    #[test]
+    #[should_panic(expected = "full range")]
+    fn limited_range_alpha_is_rejected() {
+        AlphaEncoder::new(build(Setup::default()), build(Setup::default()));
+    }
+}
@@ -1,3 +1,4 @@
+use alloc::vec::Vec;
 use x264::*;
 
 #[derive(Debug, Hash, Eq, PartialEq, Copy, Clone)]
@@ -20,10 +21,16 @@ pub enum Colorspace {
     NV16 = X264_CSP_NV16,
     /// A single plane whose bytes follow the pattern YUYV pattern, which means
     /// the U and V parts are 2x1 subsampled.
+    ///
+    /// Only available when the linked x264 supports it (build 149 or later),
+    /// which is detected at build time.
     #[cfg(yuyv)]
     YUYV = X264_CSP_YUYV,
     /// A single plane whose bytes follow the pattern UYVY pattern, which means
     /// the U and V parts are 2x1 subsampled.
+    ///
+    /// Only available when the linked x264 supports it (build 149 or later),
+    /// which is detected at build time.
     #[cfg(yuyv)]
     UYVY = X264_CSP_UYVY,
     /// A packed 32-bit UYVY plane with 10-bit components, and 2 padding bits.
@@ -40,6 +47,43 @@ pub enum Colorspace {
     RGB = X264_CSP_RGB,
 }
 
+impl Colorspace {
+    /// How the chroma of this colorspace is subsampled.
+    pub fn chroma_format(self) -> ChromaFormat {
+        use self::Colorspace::*;
+
+        match self {
+            I420 | YV12 | NV12 | NV21 => ChromaFormat::Yuv420,
+            I422 | YV16 | NV16 | V210 => ChromaFormat::Yuv422,
+            #[cfg(yuyv)]
+            YUYV | UYVY => ChromaFormat::Yuv422,
+            I444 | YV24 | BGR | BGRA | RGB => ChromaFormat::Yuv444,
+        }
+    }
+
+    // The number of planes, the multiples that the width and height must be,
+    // and for each plane, the bytes per horizontal multiple and the rows per
+    // vertical multiple, at 8 bits per component.
+    pub(crate) fn layout(self) -> (usize, i32, i32, &'static [i32], &'static [i32]) {
+        //TODO: Get someone who knows what they're doing to verify this.
+
+        use self::Colorspace::*;
+
+        match self {
+            I420 | YV12 => (3, 2, 2, &[2, 1, 1], &[2, 1, 1]),
+            NV12 | NV21 => (2, 2, 2, &[2, 2], &[2, 1]),
+            I422 | YV16 => (3, 2, 1, &[2, 1, 1], &[1, 1, 1]),
+            NV16 => (2, 2, 1, &[2, 2], &[1, 1]),
+            #[cfg(yuyv)]
+            YUYV | UYVY => (1, 2, 1, &[4], &[1]),
+            V210 => (1, 2, 1, &[], &[1]),
+            I444 | YV24 => (3, 1, 1, &[1, 1, 1], &[1, 1, 1]),
+            BGR | RGB => (1, 1, 1, &[3], &[1]),
+            BGRA => (1, 1, 1, &[4], &[1]),
+        }
+    }
+}
+
 #[derive(Debug, Hash, Eq, PartialEq, Copy, Clone)]
 /// The image's colorspace plus some extra encoding options.
 pub struct Encoding {
@@ -47,6 +91,13 @@ pub struct Encoding {
 }
 
 impl Encoding {
+    /// Makes an encoding from a colorspace with an encoding option, such as
+    /// `Modifier::HighDepth` for 16-bit samples. More can be added with
+    /// `add_modifier`.
+    pub fn with_modifier(csp: Colorspace, modifier: Modifier) -> Self {
+        Self::from(csp).add_modifier(modifier)
+    }
+
     /// Add an encoding option.
     pub fn add_modifier(mut self, modifier: Modifier) -> Self {
         self.raw |= modifier as i32;
@@ -67,7 +118,59 @@ impl Encoding {
     /// Gets the colorspace of the encoding.
     pub fn colorspace(self) -> Colorspace {
         use core::mem;
-        unsafe { mem::transmute(self.raw as u32 % X264_CSP_MAX) }
+        unsafe { mem::transmute(self.raw as u32 & X264_CSP_MASK) }
+    }
+
+    /// Whether the linked build of x264 can encode images of this
+    /// encoding's chroma format.
+    ///
+    /// x264 can't be asked which bit depths it supports, so that isn't
+    /// checked.
+    pub fn is_supported(self) -> bool {
+        let supported = unsafe { x264_chroma_format };
+        supported == 0 || supported == self.colorspace().chroma_format() as i32
+    }
+
+    /// The alignment, in bytes, that plane pointers and strides should have
+    /// for the fastest input.
+    ///
+    /// x264 copies every input image into its own padded frames, so any
+    /// alignment and padding works. Aligning to this, which suits the widest
+    /// SIMD that x264 uses (AVX-512), just makes that copy cheaper.
+    pub fn row_alignment(self) -> usize {
+        64
+    }
+
+    /// The minimum stride and the number of rows of each plane of an image
+    /// with this encoding, in order, for allocating buffers.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the width or height doesn't suit the colorspace's
+    /// subsampling.
+    pub fn plane_layout(self, width: i32, height: i32) -> Vec<PlaneLayout> {
+        let (pc, wm, hm, _, _) = self.colorspace().layout();
+        assert!(width % wm == 0 && height % hm == 0);
+        (0..pc).map(|i| self.plane(i, width, height)).collect()
+    }
+
+    // The layout of the ith plane, for dimensions that suit the subsampling.
+    pub(crate) fn plane(self, i: usize, width: i32, height: i32) -> PlaneLayout {
+        let (_, wm, hm, ws, hs) = self.colorspace().layout();
+        let depth = if self.has_modifier(Modifier::HighDepth) {
+            2
+        } else {
+            1
+        };
+
+        PlaneLayout {
+            // V210 packs 48 pixels into 128 bytes.
+            stride: match self.colorspace() {
+                Colorspace::V210 => (width + 47) / 48 * 128,
+                _ => depth * width / wm * ws[i],
+            },
+            rows: height / hm * hs[i],
+        }
     }
 
     #[doc(hidden)]
@@ -87,6 +190,35 @@ impl From<Colorspace> for Encoding {
     }
 }
 
+#[derive(Debug, Hash, Eq, PartialEq, Copy, Clone)]
+/// The minimum dimensions of one plane of an image.
+pub struct PlaneLayout {
+    /// The minimum stride, in bytes.
+    pub stride: i32,
+    /// The number of rows.
+    pub rows: i32,
+}
+
+impl PlaneLayout {
+    /// The minimum size of the plane, in bytes.
+    pub fn size(&self) -> usize {
+        self.stride as usize * self.rows as usize
+    }
+}
+
+#[derive(Debug, Hash, Eq, PartialEq, Copy, Clone)]
+#[repr(i32)]
+/// How much the chroma planes are subsampled relative to the luma plane.
+pub enum ChromaFormat {
+    /// 2x2 subsampled chroma.
+    Yuv420 = X264_CSP_I420 as i32,
+    /// 2x1 subsampled chroma.
+    Yuv422 = X264_CSP_I422 as i32,
+    /// Full resolution chroma.
+    Yuv444 = X264_CSP_I444 as i32,
+}
+
+#[derive(Debug, Hash, Eq, PartialEq, Copy, Clone)]
 #[repr(i32)]
 /// Some extra encoding options.
 pub enum Modifier {
@@ -95,3 +227,46 @@ pub enum Modifier {
     /// Vertically flips the image.
     VerticalFlip = X264_CSP_VFLIP as i32,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layout(stride: i32, rows: i32) -> PlaneLayout {
+        PlaneLayout { stride, rows }
+    }
+
+    #[test]
+    fn i420_1080p_layout() {
+        let encoding = Encoding::from(Colorspace::I420);
+        assert_eq!(
+            encoding.plane_layout(1920, 1080),
+            [layout(1920, 1080), layout(960, 540), layout(960, 540)]
+        );
+    }
+
+    #[test]
+    fn nv12_1080p_layout() {
+        let encoding = Encoding::from(Colorspace::NV12);
+        assert_eq!(
+            encoding.plane_layout(1920, 1080),
+            [layout(1920, 1080), layout(1920, 540)]
+        );
+    }
+
+    #[test]
+    fn modifiers_keep_the_colorspace() {
+        let encoding = Encoding::with_modifier(Colorspace::V210, Modifier::HighDepth)
+            .add_modifier(Modifier::VerticalFlip);
+        assert_eq!(encoding.colorspace(), Colorspace::V210);
+    }
+
+    #[test]
+    fn high_depth_nv12_layout() {
+        let encoding = Encoding::with_modifier(Colorspace::NV12, Modifier::HighDepth);
+        assert_eq!(
+            encoding.plane_layout(1920, 1080),
+            [layout(3840, 1080), layout(3840, 540)]
+        );
+    }
+}
@@ -0,0 +1,202 @@
+use crate::{Colorspace, Image, Plane};
+use alloc::vec::Vec;
+
+#[derive(Clone, Copy, Hash, Debug, Eq, PartialEq)]
+/// The matrix used to turn RGB into limited-range YUV.
+pub enum ColorMatrix {
+    /// ITU-R BT.601, for standard definition video.
+    Bt601,
+    /// ITU-R BT.709, for high definition video.
+    Bt709,
+}
+
+impl ColorMatrix {
+    // 8-bit fixed-point coefficients for Y, U and V.
+    fn coefficients(self) -> [[i32; 3]; 3] {
+        match self {
+            ColorMatrix::Bt601 => [[66, 129, 25], [-38, -74, 112], [112, -94, -18]],
+            ColorMatrix::Bt709 => [[47, 157, 16], [-26, -87, 112], [112, -102, -10]],
+        }
+    }
+}
+
+/// An I420 image that owns its planes.
+pub struct OwnedImage {
+    width: i32,
+    height: i32,
+    y: Vec<u8>,
+    u: Vec<u8>,
+    v: Vec<u8>,
+}
+
+impl OwnedImage {
+    /// Borrows the planes as an image that can be fed to the encoder.
+    pub fn image(&self) -> Image<'_> {
+        let (w, h) = (self.width, self.height);
+        Image::new(
+            Colorspace::I420,
+            w,
+            h,
+            None,
+            &[
+                Plane {
+                    stride: w,
+                    data: &self.y,
+                },
+                Plane {
+                    stride: w / 2,
+                    data: &self.u,
+                },
+                Plane {
+                    stride: w / 2,
+                    data: &self.v,
+                },
+            ],
+        )
+    }
+
+    /// The width of the image.
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+    /// The height of the image.
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+    /// The Y plane.
+    pub fn y(&self) -> &[u8] {
+        &self.y
+    }
+    /// The 2x2 subsampled U plane.
+    pub fn u(&self) -> &[u8] {
+        &self.u
+    }
+    /// The 2x2 subsampled V plane.
+    pub fn v(&self) -> &[u8] {
+        &self.v
+    }
+}
+
+/// Converts a tightly packed 24-bit RGB image to I420.
+///
+/// Chroma is taken from the average of each 2x2 block of pixels.
+///
+/// # Panics
+///
+/// Panics if the width or height is odd, or if `rgb` is too short.
+pub fn rgb_to_i420(rgb: &[u8], width: i32, height: i32, matrix: ColorMatrix) -> OwnedImage {
+    assert!(width % 2 == 0 && height % 2 == 0);
+
+    let (w, h) = (width as usize, height as usize);
+    assert!(rgb.len() >= w * h * 3);
+
+    let [ky, ku, kv] = matrix.coefficients();
+    let dot = |k: [i32; 3], [r, g, b]: [i32; 3]| (k[0] * r + k[1] * g + k[2] * b + 128) >> 8;
+    let pixel = |x: usize, y: usize| {
+        let i = (y * w + x) * 3;
+        [rgb[i] as i32, rgb[i + 1] as i32, rgb[i + 2] as i32]
+    };
+
+    let mut luma = Vec::with_capacity(w * h);
+    for y in 0..h {
+        for x in 0..w {
+            luma.push((dot(ky, pixel(x, y)) + 16) as u8);
+        }
+    }
+
+    let mut u = Vec::with_capacity(w * h / 4);
+    let mut v = Vec::with_capacity(w * h / 4);
+    for y in (0..h).step_by(2) {
+        for x in (0..w).step_by(2) {
+            let mut sum = [0; 3];
+            for &(dx, dy) in &[(0, 0), (1, 0), (0, 1), (1, 1)] {
+                let p = pixel(x + dx, y + dy);
+                for (s, v) in sum.iter_mut().zip(p) {
+                    *s += v;
+                }
+            }
+            let avg = [(sum[0] + 2) / 4, (sum[1] + 2) / 4, (sum[2] + 2) / 4];
+            u.push((dot(ku, avg) + 128) as u8);
+            v.push((dot(kv, avg) + 128) as u8);
+        }
+    }
+
+    OwnedImage {
+        width,
+        height,
+        y: luma,
+        u,
+        v,
+    }
+}
+
+/// Converts a tightly packed grayscale (luma only) image to I420, with
+/// neutral chroma.
+///
+/// This is for sources such as depth maps or monochrome cameras. The luma is
+/// copied as is, so it should already be in the limited range.
+///
+/// # Panics
+///
+/// Panics if the width or height is odd, or if `y` is too short.
+pub fn gray_to_i420(y: &[u8], width: i32, height: i32) -> OwnedImage {
+    assert!(width % 2 == 0 && height % 2 == 0);
+
+    let (w, h) = (width as usize, height as usize);
+    assert!(y.len() >= w * h);
+
+    OwnedImage {
+        width,
+        height,
+        y: y[..w * h].to_vec(),
+        u: alloc::vec![128; w * h / 4],
+        v: alloc::vec![128; w * h / 4],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Converts a 2x2 image of one color, returning its Y, U and V.
+    fn convert(rgb: [u8; 3], matrix: ColorMatrix) -> [u8; 3] {
+        let pixels: Vec<u8> = rgb.iter().cycle().take(12).cloned().collect();
+        let image = rgb_to_i420(&pixels, 2, 2, matrix);
+        assert!(image.y().iter().all(|&y| y == image.y()[0]));
+        [image.y()[0], image.u()[0], image.v()[0]]
+    }
+
+    fn assert_close(actual: [u8; 3], expected: [u8; 3]) {
+        for (a, e) in actual.iter().zip(&expected) {
+            assert!(
+                (*a as i32 - *e as i32).abs() <= 1,
+                "{:?} != {:?}",
+                actual,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn solid_colors_bt601() {
+        assert_close(convert([0, 0, 0], ColorMatrix::Bt601), [16, 128, 128]);
+        assert_close(
+            convert([255, 255, 255], ColorMatrix::Bt601),
+            [235, 128, 128],
+        );
+        assert_close(convert([255, 0, 0], ColorMatrix::Bt601), [82, 90, 240]);
+        assert_close(convert([0, 255, 0], ColorMatrix::Bt601), [145, 54, 34]);
+        assert_close(convert([0, 0, 255], ColorMatrix::Bt601), [41, 240, 110]);
+    }
+
+    #[test]
+    fn solid_colors_bt709() {
+        assert_close(
+            convert([255, 255, 255], ColorMatrix::Bt709),
+            [235, 128, 128],
+        );
+        assert_close(convert([255, 0, 0], ColorMatrix::Bt709), [63, 102, 240]);
+        assert_close(convert([0, 255, 0], ColorMatrix::Bt709), [173, 42, 26]);
+        assert_close(convert([0, 0, 255], ColorMatrix::Bt709), [32, 240, 118]);
+    }
+}
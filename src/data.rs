@@ -1,3 +1,4 @@
+use alloc::vec::Vec;
 use core::marker::PhantomData;
 use core::slice;
 use x264::*;
@@ -9,6 +10,7 @@ use x264::*;
 pub struct Data<'a> {
     ptr: *mut x264_nal_t,
     len: usize,
+    annexb: bool,
     spooky: PhantomData<&'a [x264_nal_t]>,
 }
 
@@ -20,10 +22,17 @@ impl<'a> Data<'a> {
         Data {
             ptr,
             len,
+            annexb: true,
             spooky: PhantomData,
         }
     }
 
+    // Whether the units have start codes rather than length prefixes.
+    pub(crate) fn with_annexb(mut self, annexb: bool) -> Self {
+        self.annexb = annexb;
+        self
+    }
+
     /// The length (in NAL units, **not** in bytes) of this data sequence.
     pub fn len(&self) -> usize {
         self.len
@@ -53,6 +62,12 @@ impl<'a> Data<'a> {
                 H => Priority::High,
                 _ => Priority::Highest,
             },
+            unit_type: UnitType::from_raw(nal.i_type),
+            prefix: if self.annexb && nal.b_long_startcode == 0 {
+                3
+            } else {
+                4
+            },
             payload: unsafe { slice::from_raw_parts(nal.p_payload, nal.i_payload as usize) },
         }
     }
@@ -79,6 +94,8 @@ impl<'a> Data<'a> {
 /// A single NAL unit.
 pub struct Unit<'a> {
     priority: Priority,
+    unit_type: UnitType,
+    prefix: usize,
     payload: &'a [u8],
 }
 
@@ -87,6 +104,40 @@ impl<'a> Unit<'a> {
     pub fn priority(&self) -> Priority {
         self.priority
     }
+
+    /// What this unit contains.
+    pub fn unit_type(&self) -> UnitType {
+        self.unit_type
+    }
+
+    /// The raw byte sequence payload, which is the unit without its start
+    /// code (or length), its header or any emulation prevention bytes.
+    ///
+    /// This is what parsers of SEI messages or parameter sets expect.
+    pub fn rbsp(&self) -> Vec<u8> {
+        let mut rbsp = Vec::with_capacity(self.payload.len());
+        let mut zeros = 0;
+
+        // Skip the one-byte header too.
+        for &byte in &self.without_prefix()[1..] {
+            // An emulation prevention byte follows every two zeros that
+            // precede something that is at most 3.
+            if zeros >= 2 && byte == 3 {
+                zeros = 0;
+                continue;
+            }
+            zeros = if byte == 0 { zeros + 1 } else { 0 };
+            rbsp.push(byte);
+        }
+
+        rbsp
+    }
+
+    // The unit without its start code or, when not using Annex B, its
+    // 4-byte length.
+    pub(crate) fn without_prefix(&self) -> &'a [u8] {
+        &self.payload[self.prefix..]
+    }
 }
 
 impl<'a> AsRef<[u8]> for Unit<'a> {
@@ -95,6 +146,17 @@ impl<'a> AsRef<[u8]> for Unit<'a> {
     }
 }
 
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+/// How the units are delimited in the encoded data.
+pub enum NalFormat {
+    /// Each unit starts with a start code, as raw `.h264` streams and MPEG-TS
+    /// expect.
+    AnnexB,
+    /// Each unit starts with its 4-byte big-endian length, as MP4 and other
+    /// ISO BMFF containers expect.
+    LengthPrefixed,
+}
+
 #[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
 #[repr(i32)]
 /// The importance of a given unit.
@@ -108,3 +170,66 @@ pub enum Priority {
     /// Extremely important.
     Highest = 3,
 }
+
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+#[non_exhaustive]
+/// The kind of data a unit holds.
+pub enum UnitType {
+    /// A slice of a picture that isn't IDR.
+    Slice,
+    /// A slice of an IDR picture.
+    SliceIdr,
+    /// Supplemental enhancement information.
+    Sei,
+    /// A sequence parameter set.
+    Sps,
+    /// A picture parameter set.
+    Pps,
+    /// An access unit delimiter.
+    Aud,
+    /// Filler data.
+    Filler,
+    /// Anything else.
+    Unknown,
+}
+
+impl UnitType {
+    fn from_raw(raw: i32) -> Self {
+        match raw {
+            1 => UnitType::Slice,
+            5 => UnitType::SliceIdr,
+            6 => UnitType::Sei,
+            7 => UnitType::Sps,
+            8 => UnitType::Pps,
+            9 => UnitType::Aud,
+            12 => UnitType::Filler,
+            _ => UnitType::Unknown,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sei(payload: &[u8]) -> Unit {
+        Unit {
+            priority: Priority::Disposable,
+            unit_type: UnitType::Sei,
+            prefix: 4,
+            payload,
+        }
+    }
+
+    #[test]
+    fn rbsp_strips_emulation_prevention() {
+        let unit = sei(&[0, 0, 0, 1, 0x06, 0, 0, 3, 1, 0, 0, 3, 0, 5]);
+        assert_eq!(unit.rbsp(), [0, 0, 1, 0, 0, 0, 5]);
+    }
+
+    #[test]
+    fn rbsp_keeps_other_threes() {
+        let unit = sei(&[0, 0, 0, 1, 0x06, 1, 3, 0, 3, 0, 5]);
+        assert_eq!(unit.rbsp(), [1, 3, 0, 3, 0, 5]);
+    }
+}
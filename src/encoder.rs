@@ -1,4 +1,11 @@
-use crate::{Data, Encoding, Error, Image, Picture, Result, Setup};
+use crate::{
+    sei, BPyramid, ChromaFormat, Data, EncodeStats, Encoding, Error, FrameType, GlobalMetrics,
+    Image, Level, NalFormat, PicStruct, Picture, Profile, Pulldown, Result, Setup, Unit, UnitType,
+};
+use alloc::string::String;
+use alloc::vec::Vec;
+#[cfg(feature = "bytes")]
+use bytes::BufMut;
 use core::{mem::MaybeUninit, ptr};
 use x264::*;
 
@@ -6,6 +13,18 @@ use x264::*;
 pub struct Encoder {
     raw: *mut x264_t,
     params: x264_param_t,
+    pub(crate) forced_frame_types: Vec<(i64, FrameType)>,
+    pub(crate) pulldown: Pulldown,
+    pub(crate) first_frame_qp: Option<i32>,
+    submitted: u64,
+    emitted: u64,
+    gop_position: u64,
+    started: bool,
+    finished: bool,
+    force_idr: bool,
+    pts_offset: i64,
+    stats: EncodeStats,
+    metrics: GlobalMetrics,
 }
 
 unsafe impl Send for Encoder {}
@@ -23,20 +42,214 @@ impl Encoder {
         let mut params = MaybeUninit::uninit();
         unsafe { x264_encoder_parameters(raw, params.as_mut_ptr()) };
         let params = unsafe { params.assume_init() };
-        Self { raw, params }
+        Self {
+            raw,
+            params,
+            forced_frame_types: Vec::new(),
+            pulldown: Pulldown::None,
+            first_frame_qp: None,
+            submitted: 0,
+            emitted: 0,
+            gop_position: 0,
+            started: false,
+            finished: false,
+            force_idr: false,
+            pts_offset: 0,
+            stats: EncodeStats::default(),
+            metrics: GlobalMetrics::default(),
+        }
     }
 
     /// Feeds a frame to the encoder.
     ///
+    /// Returns `None` if the frame was buffered (e.g. by the lookahead or for
+    /// bframes), in which case there is nothing to write yet.
+    ///
+    /// x264 repeats the headers before every keyframe by default. If that is
+    /// turned off, the stream is unplayable unless they are written first
+    /// (see `start`), which debug builds assert.
+    ///
+    /// # Errors
+    ///
+    /// Fails with `Error::IncompatibleFrameType` if the image forces a frame
+    /// type that the encoder's settings can't produce: a bframe without any
+    /// bframes allowed, or a referenced bframe without a `BPyramid`. Fails
+    /// with `Error::EncoderFinished` if the encoder can't be fed any more
+    /// (see `drain`).
+    ///
     /// # Panics
     ///
     /// Panics if there is a mismatch between the image and the encoder
-    /// regarding width, height or colorspace.
-    pub fn encode(&mut self, pts: i64, image: Image) -> Result<(Data, Picture)> {
+    /// regarding width, height or colorspace. Debug builds also panic if the
+    /// image is full range but the encoder doesn't signal it, or if a plane's
+    /// stride is smaller than `Encoding::plane_layout` gives (which only an
+    /// image made with `Image::new_unchecked` can have).
+    pub fn encode(&mut self, pts: i64, image: Image) -> Result<Option<(Data, Picture)>> {
+        self.check_image(&image);
+        unsafe { self.encode_unchecked(pts, image) }
+    }
+
+    fn check_image(&self, image: &Image) {
         assert_eq!(image.width(), self.width());
         assert_eq!(image.height(), self.height());
         assert_eq!(image.encoding(), self.encoding());
-        unsafe { self.encode_unchecked(pts, image) }
+        debug_assert!(
+            self.started || self.params.b_repeat_headers != 0,
+            "the headers must be written before any frames"
+        );
+        debug_assert!(
+            !image.full_range() || self.params.vui.b_fullrange != 0,
+            "full-range images need the encoder to signal full range"
+        );
+        debug_assert!(
+            image.strides_fit(),
+            "an image plane's stride is too small for its encoding"
+        );
+    }
+
+    /// Feeds a frame to the encoder, forcing it to be an intra frame that
+    /// isn't IDR.
+    ///
+    /// Unlike an IDR frame, this doesn't stop later frames from referencing
+    /// earlier ones, so it refreshes the picture (e.g. to recover from errors)
+    /// without starting a new GOP. Otherwise, this is the same as `encode`.
+    ///
+    /// That only holds within the minimum keyframe interval, or with
+    /// `Setup::open_gop`. With closed GOPs (the default), x264 turns an intra
+    /// frame that comes at least `min_keyframe_interval` frames after the last
+    /// keyframe into an IDR frame, which does start a new GOP.
+    pub fn encode_intra(&mut self, pts: i64, mut image: Image) -> Result<Option<(Data, Picture)>> {
+        image.set_frame_type(FrameType::I);
+        self.encode(pts, image)
+    }
+
+    /// Feeds a frame to the encoder, telling it which macroblocks are the
+    /// same as in the previous image, so that it can skip analysing them.
+    ///
+    /// This suits screen content, which is often mostly static. It only has
+    /// an effect if macroblock info is enabled (see `Setup::screen_content`),
+    /// and if both images have the same encoding and size. Otherwise, this is
+    /// the same as `encode`.
+    pub fn encode_if_changed(
+        &mut self,
+        pts: i64,
+        mut image: Image,
+        prev: &Image,
+    ) -> Result<Option<(Data, Picture)>> {
+        if self.params.analyse.b_mb_info != 0 {
+            image.mark_unchanged(prev);
+        }
+        self.encode(pts, image)
+    }
+
+    /// Feeds a frame to the encoder, throwing away any output.
+    ///
+    /// This is for measuring the cost of encoding itself, e.g. when
+    /// benchmarking, so none of the output is wrapped. The frame counters are
+    /// still updated, but the statistics and metrics aren't. Otherwise, this
+    /// is the same as `encode`.
+    pub fn encode_discard(&mut self, pts: i64, image: Image) -> Result<()> {
+        self.check_image(&image);
+
+        let mut len = 0;
+        let mut stuff = MaybeUninit::uninit();
+        let mut raw = MaybeUninit::uninit();
+
+        let err = unsafe { self.submit(pts, image, &mut stuff, &mut len, &mut raw) }?;
+
+        if err > 0 {
+            let raw = unsafe { raw.assume_init() };
+            self.advance_gop(raw.b_keyframe != 0);
+            self.emitted += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Feeds a frame to the encoder, splitting any output into its units.
+    ///
+    /// With several slices per frame (see `Setup::slices`), each slice is its
+    /// own unit, so they can be packetized independently. Any other units,
+    /// such as SEI, are included in order. Otherwise, this is the same as
+    /// `encode`.
+    pub fn encode_slices(
+        &mut self,
+        pts: i64,
+        image: Image,
+    ) -> Result<Option<(Vec<Unit>, Picture)>> {
+        Ok(self.encode(pts, image)?.map(|(data, picture)| {
+            let units = (0..data.len()).map(|i| data.unit(i)).collect();
+            (units, picture)
+        }))
+    }
+
+    /// Feeds a frame to the encoder, copying any output into `buf`.
+    ///
+    /// With a preallocated buffer, such as a ring buffer, this doesn't
+    /// allocate, and the output isn't tied to the encoder's lifetime.
+    /// As with `encode`, `None` means the frame was buffered.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buf` can't hold the output, or under the same conditions as
+    /// `encode`.
+    #[cfg(feature = "bytes")]
+    pub fn encode_into<B: BufMut>(
+        &mut self,
+        pts: i64,
+        image: Image,
+        buf: &mut B,
+    ) -> Result<Option<Picture>> {
+        Ok(self.encode(pts, image)?.map(|(data, picture)| {
+            buf.put_slice(data.entirety());
+            picture
+        }))
+    }
+
+    /// Feeds a frame to the encoder, with its timestamp in milliseconds.
+    ///
+    /// The timestamp is converted to the encoder's timebase, as with
+    /// `ms_to_pts`. Otherwise, this is the same as `encode`.
+    pub fn encode_ms(&mut self, ms: i64, image: Image) -> Result<Option<(Data, Picture)>> {
+        let pts = self.ms_to_pts(ms);
+        self.encode(pts, image)
+    }
+
+    /// Feeds a frame to the encoder, timestamped as the next frame at the
+    /// encoder's constant framerate.
+    ///
+    /// The `n`th frame fed to the encoder (counting from zero, and including
+    /// any fed through the other methods) gets the timestamp of `n` frame
+    /// durations. After a `reset`, this carries on from the last timestamp
+    /// given. Otherwise, this is the same as `encode`.
+    pub fn encode_next(&mut self, image: Image) -> Result<Option<(Data, Picture)>> {
+        let pts = self.pts_offset + self.frame_pts(self.submitted);
+        self.encode(pts, image)
+    }
+
+    // The timestamp of the nth frame at the encoder's framerate, rounding down.
+    fn frame_pts(&self, n: u64) -> i64 {
+        let num = self.params.i_timebase_num as i128 * self.params.i_fps_num as i128;
+        let den = self.params.i_timebase_den as i128 * self.params.i_fps_den as i128;
+        (n as i128 * den).div_euclid(num) as i64
+    }
+
+    /// Converts milliseconds into the encoder's timebase, rounding down.
+    ///
+    /// Note that x264 uses the inverse of the framerate as the timebase unless
+    /// variable framerate input or pulldown is enabled.
+    pub fn ms_to_pts(&self, ms: i64) -> i64 {
+        let num = self.params.i_timebase_num as i128;
+        let den = self.params.i_timebase_den as i128;
+        (ms as i128 * den).div_euclid(1000 * num) as i64
+    }
+
+    /// Converts a timestamp in the encoder's timebase into milliseconds,
+    /// rounding down.
+    pub fn pts_to_ms(&self, pts: i64) -> i64 {
+        let num = self.params.i_timebase_num as i128;
+        let den = self.params.i_timebase_den as i128;
+        (pts as i128 * num * 1000).div_euclid(den) as i64
     }
 
     /// Feeds a frame to the encoder.
@@ -45,53 +258,303 @@ impl Encoder {
     ///
     /// The caller must ensure that the width, height *and* colorspace
     /// of the image are the same as that of the encoder.
-    pub unsafe fn encode_unchecked(&mut self, pts: i64, image: Image) -> Result<(Data, Picture)> {
+    pub unsafe fn encode_unchecked(
+        &mut self,
+        pts: i64,
+        image: Image,
+    ) -> Result<Option<(Data, Picture)>> {
+        let mut len = 0;
+        let mut stuff = MaybeUninit::uninit();
+        let mut raw = MaybeUninit::uninit();
+
+        let err = unsafe { self.submit(pts, image, &mut stuff, &mut len, &mut raw) }?;
+
+        // The output picture is only filled in if a frame was returned.
+        if err == 0 {
+            Ok(None)
+        } else {
+            let (stuff, raw) = unsafe { (stuff.assume_init(), raw.assume_init()) };
+            let data = self.data(stuff, len);
+            let picture = self.picture(raw);
+            self.record(err, &picture);
+            Ok(Some((data, picture)))
+        }
+    }
+
+    // Hands a frame to x264, returning the size of any output, which is only
+    // written to the out parameters if that is positive. The image must suit
+    // the encoder, as for `encode_unchecked`.
+    unsafe fn submit(
+        &mut self,
+        pts: i64,
+        image: Image,
+        stuff: &mut MaybeUninit<*mut x264_nal_t>,
+        len: &mut i32,
+        raw: &mut MaybeUninit<x264_picture_t>,
+    ) -> Result<i32> {
+        if self.finished {
+            return Err(Error::EncoderFinished);
+        }
+
         let image_raw = image.raw();
+        let frame_type = match *image.frame_type() {
+            FrameType::Auto if self.force_idr => FrameType::IDR,
+            FrameType::Auto => self.forced_frame_type(pts),
+            frame_type => frame_type,
+        };
+        self.check_frame_type(frame_type)?;
 
         let mut picture = MaybeUninit::uninit();
         unsafe { x264_picture_init(picture.as_mut_ptr()) };
         let mut picture = unsafe { picture.assume_init() };
         picture.i_pts = pts;
         picture.img = image_raw;
-        picture.i_type = image.frame_type().clone() as i32;
-
-        let mut len = 0;
-        let mut stuff = MaybeUninit::uninit();
-        let mut raw = MaybeUninit::uninit();
+        picture.i_type = frame_type as i32;
+        let qp = match image.forced_qp() {
+            None if self.submitted == 0 => self.first_frame_qp,
+            qp => qp,
+        };
+        picture.i_qpplus1 = qp.map_or(X264_QP_AUTO as i32, |qp| qp + 1);
+        picture.i_pic_struct = match image.pic_struct() {
+            PicStruct::Auto => self.pulldown.pic_struct(self.submitted),
+            pic_struct => pic_struct,
+        } as i32;
+        // Allocated last, since x264 takes ownership of it.
+        picture.extra_sei = image.raw_sei();
+        picture.prop.mb_info = image.raw_mb_info();
+        picture.prop.mb_info_free = Some(sei::free);
 
         let err = unsafe {
             x264_encoder_encode(
                 self.raw,
                 stuff.as_mut_ptr(),
-                &mut len,
+                len,
                 &mut picture,
                 raw.as_mut_ptr(),
             )
         };
 
         if err < 0 {
-            Err(Error)
+            // x264 only takes ownership of what it was given once it has
+            // accepted the frame.
+            unsafe {
+                sei::free_raw(&picture.extra_sei);
+                sei::free(picture.prop.mb_info as *mut _);
+            }
+            return Err(Error::Encode);
+        }
+
+        self.submitted += 1;
+        self.force_idr = false;
+        Ok(err)
+    }
+
+    fn check_frame_type(&self, frame_type: FrameType) -> Result<()> {
+        let incompatible = match frame_type {
+            // x264 would silently turn these into P-frames.
+            FrameType::B => self.params.i_bframe == 0,
+            FrameType::Bref => {
+                self.params.i_bframe == 0 || self.params.i_bframe_pyramid == BPyramid::None as i32
+            }
+            _ => false,
+        };
+
+        if incompatible {
+            Err(Error::IncompatibleFrameType)
         } else {
-            let stuff = unsafe { stuff.assume_init() };
-            let raw = unsafe { raw.assume_init() };
-            let data = unsafe { Data::from_raw_parts(stuff, len as usize) };
-            let picture = unsafe { Picture::from_raw(raw) };
-            Ok((data, picture))
+            Ok(())
+        }
+    }
+
+    fn forced_frame_type(&self, pts: i64) -> FrameType {
+        match self
+            .forced_frame_types
+            .binary_search_by_key(&pts, |&(pts, _)| pts)
+        {
+            Ok(i) => self.forced_frame_types[i].1,
+            Err(_) => FrameType::Auto,
         }
     }
 
+    /// Reapplies the encoder's parameters to its rate control, e.g. at a
+    /// segment boundary.
+    ///
+    /// x264 can't reset its rate control outright. This reconfigures the
+    /// encoder with its current parameters, which recomputes the bitrate, CRF
+    /// and VBV settings, but keeps the complexity and bit budget history used
+    /// by average bitrate mode, as well as the current VBV buffer fullness.
+    pub fn reset_rate_control(&mut self) -> Result<()> {
+        let mut params = self.params;
+        self.reconfigure(&mut params)
+    }
+
+    /// Prepares the encoder for a new, independent sequence, such as the next
+    /// of many clips with the same size, without reopening it.
+    ///
+    /// Any delayed frames are encoded and thrown away, so drain the encoder
+    /// first to keep them. The frame counters, statistics and pulldown
+    /// pattern start over, the rate control is reset as with
+    /// `reset_rate_control`, and the next frame is forced to be an IDR frame.
+    ///
+    /// x264 still sees a single stream, so timestamps must keep increasing
+    /// across a reset.
+    ///
+    /// # Errors
+    ///
+    /// Fails with `Error::Reconfigure` if threaded lookahead is enabled,
+    /// since the encoder couldn't be fed again (see `drain`), or if x264
+    /// refuses to reset the rate control.
+    pub fn reset(&mut self) -> Result<()> {
+        if self.params.i_sync_lookahead != 0 {
+            return Err(Error::Reconfigure);
+        }
+        let mut drain = self.drain();
+        while let Some(result) = drain.next() {
+            result?;
+        }
+
+        self.pts_offset += self.frame_pts(self.submitted);
+        self.submitted = 0;
+        self.emitted = 0;
+        self.stats = EncodeStats::default();
+        self.metrics = GlobalMetrics::default();
+        self.force_idr = true;
+        self.reset_rate_control()
+    }
+
+    fn reconfigure(&mut self, params: &mut x264_param_t) -> Result<()> {
+        if unsafe { x264_encoder_reconfig(self.raw, params) } < 0 {
+            return Err(Error::Reconfigure);
+        }
+
+        unsafe { x264_encoder_parameters(self.raw, &mut self.params) };
+        Ok(())
+    }
+
+    /// Gets the headers, which must be written before any frames.
+    ///
+    /// This is the same as `headers_owned`, and is meant to be called once,
+    /// before the first call to `encode`.
+    pub fn start(&mut self) -> Result<Vec<u8>> {
+        self.headers_owned()
+    }
+
     /// Gets the video headers, which should be sent first.
     pub fn headers(&mut self) -> Result<Data> {
+        self.started = true;
+
         let mut len = 0;
         let mut stuff = MaybeUninit::uninit();
 
         let err = unsafe { x264_encoder_headers(self.raw, stuff.as_mut_ptr(), &mut len) };
 
         if err < 0 {
-            Err(Error)
+            Err(Error::Encode)
         } else {
             let stuff = unsafe { stuff.assume_init() };
-            Ok(unsafe { Data::from_raw_parts(stuff, len as usize) })
+            Ok(self.data(stuff, len))
+        }
+    }
+
+    /// Gets an owned copy of the video headers.
+    ///
+    /// Unlike `headers`, the result doesn't borrow the encoder, so it can be
+    /// cached and shared (e.g. for SDP or an MP4 initialization segment).
+    pub fn headers_owned(&mut self) -> Result<Vec<u8>> {
+        self.headers().map(|headers| headers.entirety().to_vec())
+    }
+
+    /// Gets the sequence and picture parameter sets, without start codes or
+    /// length prefixes.
+    ///
+    /// These are what an MP4 `avcC` box or a DASH initialization segment is
+    /// built from.
+    ///
+    /// Encoders built from matching setups (see `Setup::assert_matches`) with
+    /// `Setup::stitchable` give byte-identical parameter sets, so chunks
+    /// encoded in parallel can share them.
+    pub fn parameter_sets(&mut self) -> Result<(Vec<u8>, Vec<u8>)> {
+        let headers = self.headers()?;
+        let (mut sps, mut pps) = (Vec::new(), Vec::new());
+
+        for i in 0..headers.len() {
+            let unit = headers.unit(i);
+            match unit.unit_type() {
+                UnitType::Sps => sps = unit.without_prefix().to_vec(),
+                UnitType::Pps => pps = unit.without_prefix().to_vec(),
+                _ => {}
+            }
+        }
+
+        Ok((sps, pps))
+    }
+
+    /// Gets the sequence parameter set on its own, without a start code or
+    /// length prefix, e.g. for SDP's `sprop-parameter-sets`.
+    pub fn sps(&mut self) -> Result<Vec<u8>> {
+        self.parameter_sets().map(|(sps, _)| sps)
+    }
+
+    /// Gets the picture parameter set on its own, without a start code or
+    /// length prefix.
+    pub fn pps(&mut self) -> Result<Vec<u8>> {
+        self.parameter_sets().map(|(_, pps)| pps)
+    }
+
+    /// Gets the parameter sets as an `AVCDecoderConfigurationRecord`, which
+    /// is the codec private data (or "extradata") that MP4's `avcC` box and
+    /// Matroska's `CodecPrivate` hold.
+    ///
+    /// The record declares 4-byte lengths, so the frames should be encoded
+    /// without Annex B start codes (see `Setup::annexb`).
+    pub fn extradata(&mut self) -> Result<Vec<u8>> {
+        let (sps, pps) = self.parameter_sets()?;
+        if sps.len() < 4 || pps.is_empty() {
+            return Err(Error::Encode);
+        }
+
+        let mut avcc = Vec::with_capacity(sps.len() + pps.len() + 15);
+        // The version, then the profile, constraints and level from the SPS.
+        avcc.extend_from_slice(&[1, sps[1], sps[2], sps[3]]);
+        // 4-byte lengths, then one SPS.
+        avcc.extend_from_slice(&[0xFF, 0xE1]);
+        avcc.extend_from_slice(&(sps.len() as u16).to_be_bytes());
+        avcc.extend_from_slice(&sps);
+        // One PPS.
+        avcc.push(1);
+        avcc.extend_from_slice(&(pps.len() as u16).to_be_bytes());
+        avcc.extend_from_slice(&pps);
+
+        // The high profiles also give the chroma format and bit depths.
+        if let 100 | 110 | 122 | 244 = sps[1] {
+            let chroma = match self.chroma_format() {
+                ChromaFormat::Yuv420 => 1,
+                ChromaFormat::Yuv422 => 2,
+                ChromaFormat::Yuv444 => 3,
+            };
+            let depth = (self.params.i_bitdepth - 8) as u8;
+            avcc.extend_from_slice(&[0xFC | chroma, 0xF8 | depth, 0xF8 | depth, 0]);
+        }
+
+        Ok(avcc)
+    }
+
+    /// Gets the RFC 6381 codec string (e.g. `avc1.640028`), for use in DASH
+    /// and HLS manifests and MIME types.
+    ///
+    /// This is read from the sequence parameter set, so it includes the
+    /// constraint flags as well as the profile and level.
+    pub fn codec_string(&mut self) -> Result<String> {
+        let (sps, _) = self.parameter_sets()?;
+        // The NAL unit header comes first.
+        match sps.get(1..4) {
+            Some(&[profile, constraints, level]) => Ok(alloc::format!(
+                "avc1.{:02x}{:02x}{:02x}",
+                profile,
+                constraints,
+                level
+            )),
+            _ => Err(Error::Encode),
         }
     }
 
@@ -110,7 +573,141 @@ impl Encoder {
     /// }
     /// ```
     pub fn flush(self) -> Flush {
-        Flush { encoder: self }
+        Flush {
+            encoder: self,
+            failed: false,
+        }
+    }
+
+    /// Handles any delayed frames without consuming the encoder, so that more
+    /// frames can be fed once they have all been returned.
+    ///
+    /// ```rust
+    /// # use x264::{Colorspace, Setup};
+    /// # let mut encoder = Setup::default().lookahead(0).build(Colorspace::RGB, 1920, 1080).unwrap();
+    /// #
+    /// let mut drain = encoder.drain();
+    ///
+    /// while let Some(result) = drain.next() {
+    ///     if let Ok((data, picture)) = result {
+    ///         // Handle data.
+    ///     }
+    /// }
+    ///
+    /// // The encoder can be fed again.
+    /// ```
+    ///
+    /// With threaded lookahead (see `Setup::lookahead`), x264 stops the
+    /// lookahead thread once it starts flushing, so if any frames were
+    /// delayed, the encoder is finished once `Drain::next` is called and
+    /// `encode` fails with `Error::EncoderFinished`. Draining an encoder with
+    /// nothing delayed has no effect.
+    pub fn drain(&mut self) -> Drain {
+        Drain {
+            encoder: self,
+            failed: false,
+        }
+    }
+
+    fn encode_delayed(&mut self) -> Option<Result<(Data, Picture)>> {
+        let mut len = 0;
+        let mut stuff = MaybeUninit::uninit();
+        let mut raw = MaybeUninit::uninit();
+
+        // With frame threads, a call can return nothing while other frames
+        // are still in flight, so keep going until one comes out.
+        let err = loop {
+            if unsafe { x264_encoder_delayed_frames(self.raw) } == 0 {
+                return None;
+            }
+            // This is where x264 stops a lookahead thread.
+            if self.params.i_sync_lookahead != 0 {
+                self.finished = true;
+            }
+
+            let err = unsafe {
+                x264_encoder_encode(
+                    self.raw,
+                    stuff.as_mut_ptr(),
+                    &mut len,
+                    ptr::null_mut(),
+                    raw.as_mut_ptr(),
+                )
+            };
+
+            if err != 0 {
+                break err;
+            }
+        };
+
+        Some(if err < 0 {
+            Err(Error::Encode)
+        } else {
+            let (data, picture) = unsafe {
+                let stuff = stuff.assume_init();
+                let raw = raw.assume_init();
+                (self.data(stuff, len), self.picture(raw))
+            };
+            self.record(err, &picture);
+            Ok((data, picture))
+        })
+    }
+
+    fn data<'a>(&self, ptr: *mut x264_nal_t, len: i32) -> Data<'a> {
+        let annexb = self.params.b_annexb != 0;
+        unsafe { Data::from_raw_parts(ptr, len as usize) }.with_annexb(annexb)
+    }
+
+    // Only called for pictures that were returned, which keeps the GOP
+    // position in step.
+    fn picture(&mut self, raw: x264_picture_t) -> Picture {
+        let psnr = self.params.analyse.b_psnr != 0;
+        let ssim = self.params.analyse.b_ssim != 0;
+        self.advance_gop(raw.b_keyframe != 0);
+        unsafe { Picture::from_raw(raw) }
+            .with_metrics(psnr, ssim)
+            .with_gop_position(self.gop_position)
+    }
+
+    fn advance_gop(&mut self, keyframe: bool) {
+        self.gop_position = if keyframe { 0 } else { self.gop_position + 1 };
+    }
+
+    fn record(&mut self, bytes: i32, picture: &Picture) {
+        // Nothing was returned if no bytes were.
+        if bytes > 0 {
+            self.emitted += 1;
+            self.stats.record(picture, bytes as usize);
+            self.metrics.record(picture);
+        }
+    }
+
+    /// The number of frames fed to the encoder so far.
+    pub fn frames_submitted(&self) -> u64 {
+        self.submitted
+    }
+
+    /// The number of frames returned by the encoder so far, including any
+    /// returned while flushing.
+    pub fn frames_emitted(&self) -> u64 {
+        self.emitted
+    }
+
+    /// Totals of the frames returned so far, by frame type.
+    pub fn stats(&self) -> &EncodeStats {
+        &self.stats
+    }
+
+    /// Averages of the PSNR and SSIM of the frames returned so far.
+    ///
+    /// Returns `None` unless the encoder was built with `Setup::psnr` or
+    /// `Setup::ssim` and has returned a frame.
+    pub fn summary_metrics(&self) -> Option<GlobalMetrics> {
+        if self.metrics.psnr().is_some() || self.metrics.ssim().is_some() {
+            Some(self.metrics)
+        } else {
+            None
+        }
     }
 
     /// The width required of any input images.
@@ -125,6 +722,67 @@ impl Encoder {
     pub fn encoding(&self) -> Encoding {
         unsafe { Encoding::from_raw(self.params.i_csp) }
     }
+
+    // Resolved parameters, which x264 may have adjusted from the ones given
+    // to the builder.
+
+    /// The number of threads used for encoding.
+    pub fn threads(&self) -> i32 {
+        self.params.i_threads
+    }
+    /// The maximum number of consecutive bframes.
+    pub fn bframes(&self) -> i32 {
+        self.params.i_bframe
+    }
+    /// The number of reference frames.
+    pub fn reference_frames(&self) -> i32 {
+        self.params.i_frame_reference
+    }
+    /// The maximum number of frames between keyframes.
+    pub fn max_keyframe_interval(&self) -> i32 {
+        self.params.i_keyint_max
+    }
+    /// The minimum number of frames between keyframes.
+    pub fn min_keyframe_interval(&self) -> i32 {
+        self.params.i_keyint_min
+    }
+    /// The average bitrate, in kilobits per second, if one was set.
+    pub fn bitrate(&self) -> i32 {
+        self.params.rc.i_bitrate
+    }
+    /// The level, multiplied by ten (e.g. `41` for level 4.1).
+    pub fn level_idc(&self) -> i32 {
+        self.params.i_level_idc
+    }
+    /// The level, as written to the sequence parameter set.
+    pub fn level(&self) -> Level {
+        // x264 picks a level from its own table, and refuses unknown ones.
+        Level::from_idc(self.params.i_level_idc).expect("x264 chose an unknown level")
+    }
+    /// Whether the video signals the full range of sample values, as set by
+    /// `Setup::full_range`.
+    pub fn full_range(&self) -> bool {
+        self.params.vui.b_fullrange != 0
+    }
+    /// How the units are delimited, as set by `Setup::annexb`.
+    ///
+    /// Muxers can check this, since writing Annex B data into an MP4 file
+    /// gives a file that won't play.
+    pub fn nal_format(&self) -> NalFormat {
+        if self.params.b_annexb != 0 {
+            NalFormat::AnnexB
+        } else {
+            NalFormat::LengthPrefixed
+        }
+    }
+    /// The chroma subsampling of the encoded video.
+    pub fn chroma_format(&self) -> ChromaFormat {
+        self.encoding().colorspace().chroma_format()
+    }
+    /// The profile, as written to the sequence parameter set.
+    pub fn profile(&self) -> Profile {
+        Profile::resolve(&self.params, self.chroma_format())
+    }
 }
 
 impl Drop for Encoder {
@@ -138,43 +796,583 @@ impl Drop for Encoder {
 /// Iterate through any delayed frames.
 pub struct Flush {
     encoder: Encoder,
+    failed: bool,
 }
 
 impl Flush {
     /// Keeps flushing.
+    ///
+    /// Returns `None` once every delayed frame has been returned. An error
+    /// means x264 failed to encode, and is fatal: any frames still delayed
+    /// are lost, and every later call returns `None`.
     #[allow(clippy::should_implement_trait)]
     pub fn next(&mut self) -> Option<Result<(Data, Picture)>> {
-        let enc = self.encoder.raw;
+        if self.failed {
+            return None;
+        }
+        let result = self.encoder.encode_delayed();
+        self.failed = matches!(result, Some(Err(_)));
+        result
+    }
 
-        if unsafe { x264_encoder_delayed_frames(enc) } == 0 {
+    /// Keeps flushing, appending the output to a buffer instead of borrowing
+    /// it from the encoder.
+    ///
+    /// Reusing one buffer for every delayed frame avoids an allocation per
+    /// frame. Otherwise, this is the same as `next`.
+    pub fn next_into(&mut self, out: &mut Vec<u8>) -> Option<Result<Picture>> {
+        self.next().map(|result| {
+            result.map(|(data, picture)| {
+                out.extend_from_slice(data.entirety());
+                picture
+            })
+        })
+    }
+
+    /// The encoder being flushed, e.g. to check its progress.
+    pub fn encoder(&self) -> &Encoder {
+        &self.encoder
+    }
+}
+
+/// Iterate through any delayed frames, keeping the encoder open.
+pub struct Drain<'a> {
+    encoder: &'a mut Encoder,
+    failed: bool,
+}
+
+impl<'a> Drain<'a> {
+    /// Keeps draining.
+    ///
+    /// As with `Flush::next`, `None` means that every delayed frame has been
+    /// returned, and an error is fatal.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<Result<(Data, Picture)>> {
+        if self.failed {
             return None;
         }
+        let result = self.encoder.encode_delayed();
+        self.failed = matches!(result, Some(Err(_)));
+        result
+    }
+}
 
-        let mut len = 0;
-        let mut stuff = MaybeUninit::uninit();
-        let mut raw = MaybeUninit::uninit();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{gray_to_i420, Colorspace, OwnedImage};
 
-        let err = unsafe {
-            x264_encoder_encode(
-                enc,
-                stuff.as_mut_ptr(),
-                &mut len,
-                ptr::null_mut(),
-                raw.as_mut_ptr(),
-            )
+    const WIDTH: i32 = 64;
+    const HEIGHT: i32 = 64;
+
+    fn build(setup: Setup) -> Encoder {
+        setup.build(Colorspace::I420, WIDTH, HEIGHT).unwrap()
+    }
+
+    // A flat gray frame, the same every time so that there are no scenecuts.
+    fn frame() -> OwnedImage {
+        gray_to_i420(&alloc::vec![128; (WIDTH * HEIGHT) as usize], WIDTH, HEIGHT)
+    }
+
+    // Feeds `count` frames, with timestamps from 0, and flushes the encoder,
+    // returning every picture in the order they came out.
+    fn encode_all(mut encoder: Encoder, count: i64) -> Vec<Picture> {
+        let frame = frame();
+        let mut pictures = Vec::new();
+
+        for pts in 0..count {
+            if let Some((_, picture)) = encoder.encode(pts, frame.image()).unwrap() {
+                pictures.push(picture);
+            }
+        }
+
+        let mut flush = encoder.flush();
+        while let Some(result) = flush.next() {
+            pictures.push(result.unwrap().1);
+        }
+
+        pictures
+    }
+
+    // The timestamps of the pictures of the given type, in order.
+    fn pts_of(pictures: &[Picture], frame_type: FrameType) -> Vec<i64> {
+        let mut pts: Vec<i64> = pictures
+            .iter()
+            .filter(|picture| picture.frame_type() == frame_type)
+            .map(|picture| picture.pts())
+            .collect();
+        pts.sort_unstable();
+        pts
+    }
+
+    #[test]
+    fn owned_headers_are_stable() {
+        let mut encoder = build(Setup::default());
+        let headers = encoder.headers_owned().unwrap();
+        assert!(!headers.is_empty());
+        assert_eq!(headers, encoder.headers_owned().unwrap());
+    }
+
+    #[test]
+    fn forced_frame_types_are_applied() {
+        let setup = Setup::default().force_frame_types(&[(3, FrameType::IDR), (7, FrameType::IDR)]);
+        let pictures = encode_all(build(setup), 10);
+        assert_eq!(pts_of(&pictures, FrameType::IDR), [0, 3, 7]);
+    }
+
+    #[test]
+    fn random_access_points_need_a_closed_gop() {
+        let setup = || {
+            Setup::default()
+                .min_keyframe_interval(1)
+                .force_frame_types(&[(5, FrameType::I)])
         };
 
-        Some(if err < 0 {
-            Err(Error)
-        } else {
-            Ok(unsafe {
-                let stuff = stuff.assume_init();
-                let raw = raw.assume_init();
-                (
-                    Data::from_raw_parts(stuff, len as usize),
-                    Picture::from_raw(raw),
-                )
+        let pictures = encode_all(build(setup()), 10);
+        let picture = pictures.iter().find(|picture| picture.pts() == 5).unwrap();
+        assert_eq!(picture.frame_type(), FrameType::IDR);
+        assert!(picture.is_random_access_point());
+
+        let pictures = encode_all(build(setup().open_gop(true)), 10);
+        let picture = pictures.iter().find(|picture| picture.pts() == 5).unwrap();
+        assert_eq!(picture.frame_type(), FrameType::I);
+        assert!(picture.keyframe());
+        assert!(!picture.is_random_access_point());
+    }
+
+    #[test]
+    fn encodes_after_draining() {
+        let mut encoder = build(Setup::default().no_sync_lookahead());
+        let frame = frame();
+        let mut returned = 0;
+
+        for pts in 0..5 {
+            if encoder.encode(pts, frame.image()).unwrap().is_some() {
+                returned += 1;
+            }
+        }
+        let mut drain = encoder.drain();
+        while let Some(result) = drain.next() {
+            result.unwrap();
+            returned += 1;
+        }
+        assert_eq!(returned, 5);
+        assert_eq!(encoder.frames_emitted(), 5);
+
+        for pts in 5..10 {
+            encoder.encode(pts, frame.image()).unwrap();
+        }
+        let mut flush = encoder.flush();
+        while let Some(result) = flush.next() {
+            result.unwrap();
+        }
+        assert_eq!(flush.encoder().frames_emitted(), 10);
+    }
+
+    #[test]
+    fn buffered_frames_return_none() {
+        let mut encoder = build(Setup::default());
+        let frame = frame();
+        assert!(encoder.encode(0, frame.image()).unwrap().is_none());
+        assert!(encoder.encode(1, frame.image()).unwrap().is_none());
+    }
+
+    #[test]
+    fn counters_include_flushed_frames() {
+        let mut encoder = build(Setup::default());
+        let frame = frame();
+        for pts in 0..10 {
+            encoder.encode(pts, frame.image()).unwrap();
+        }
+        assert_eq!(encoder.frames_submitted(), 10);
+        assert!(encoder.frames_emitted() < 10);
+
+        let mut flush = encoder.flush();
+        while let Some(result) = flush.next() {
+            result.unwrap();
+        }
+        assert_eq!(flush.encoder().frames_submitted(), 10);
+        assert_eq!(flush.encoder().frames_emitted(), 10);
+    }
+
+    #[test]
+    fn idr_interval_is_exact() {
+        let pictures = encode_all(build(Setup::default().idr_interval(4)), 12);
+        assert_eq!(pictures.len(), 12);
+        assert_eq!(pts_of(&pictures, FrameType::IDR), [0, 4, 8]);
+    }
+
+    #[test]
+    fn gop_positions_restart_at_each_idr() {
+        let pictures = encode_all(build(Setup::default().max_keyframe_interval(5)), 12);
+        assert_eq!(pictures.len(), 12);
+        assert!(pts_of(&pictures, FrameType::IDR).len() >= 3);
+
+        let mut expected = 0;
+        for picture in &pictures {
+            if picture.frame_type() == FrameType::IDR {
+                expected = 0;
+            }
+            assert_eq!(picture.gop_position(), expected);
+            assert!(picture.gop_position() < 5);
+            expected += 1;
+        }
+    }
+
+    // The types of the units in Annex B data.
+    fn unit_types(data: &[u8]) -> Vec<u8> {
+        data.windows(4)
+            .filter(|w| w[..3] == [0, 0, 1])
+            .map(|w| w[3] & 0x1f)
+            .collect()
+    }
+
+    #[test]
+    fn start_writes_the_parameter_sets() {
+        let setup = Setup::from_options_string("repeat-headers=0").unwrap();
+        let mut encoder = build(setup);
+        let headers = encoder.start().unwrap();
+        let types = unit_types(&headers);
+        assert!(types.contains(&7));
+        assert!(types.contains(&8));
+
+        // The frames that follow don't repeat them.
+        let frame = frame();
+        for pts in 0..5 {
+            if let Some((data, _)) = encoder.encode(pts, frame.image()).unwrap() {
+                assert!(!unit_types(data.entirety()).contains(&7));
+            }
+        }
+    }
+
+    #[test]
+    fn bit_exact_output_is_repeatable() {
+        let encode = || -> Vec<u8> {
+            let mut encoder = build(Setup::default().bit_exact());
+            let frame = frame();
+            let mut out = Vec::new();
+            for pts in 0..10 {
+                if let Some((data, _)) = encoder.encode(pts, frame.image()).unwrap() {
+                    out.extend_from_slice(data.entirety());
+                }
+            }
+            let mut flush = encoder.flush();
+            while let Some(result) = flush.next_into(&mut out) {
+                result.unwrap();
+            }
+            out
+        };
+        assert_eq!(encode(), encode());
+    }
+
+    #[test]
+    fn intra_frames_within_the_minimum_interval_stay_intra() {
+        let mut encoder = build(Setup::default());
+        assert!(encoder.min_keyframe_interval() > 5);
+
+        let frame = frame();
+        let mut pictures = Vec::new();
+        for pts in 0..10 {
+            let output = if pts == 5 {
+                encoder.encode_intra(pts, frame.image())
+            } else {
+                encoder.encode(pts, frame.image())
+            };
+            pictures.extend(output.unwrap().map(|(_, picture)| picture));
+        }
+        let mut flush = encoder.flush();
+        while let Some(result) = flush.next() {
+            pictures.push(result.unwrap().1);
+        }
+
+        assert_eq!(pts_of(&pictures, FrameType::I), [5]);
+        assert_eq!(pts_of(&pictures, FrameType::IDR), [0]);
+    }
+
+    // x264 only uses threaded lookahead with frame threads.
+    fn threaded_lookahead() -> Setup {
+        Setup::default().threads(2).lookahead(8)
+    }
+
+    #[test]
+    fn reset_needs_synchronous_lookahead() {
+        let mut encoder = build(threaded_lookahead());
+        assert_eq!(encoder.reset(), Err(Error::Reconfigure));
+
+        let mut encoder = build(Setup::default().no_sync_lookahead());
+        let frame = frame();
+        for pts in 0..5 {
+            encoder.encode(pts, frame.image()).unwrap();
+        }
+        encoder.reset().unwrap();
+        assert_eq!(encoder.frames_submitted(), 0);
+
+        // Only the frames after the reset come out, starting with an IDR.
+        let mut pictures = Vec::new();
+        for pts in 5..10 {
+            pictures.extend(encoder.encode(pts, frame.image()).unwrap().map(|(_, p)| p));
+        }
+        let mut flush = encoder.flush();
+        while let Some(result) = flush.next() {
+            pictures.push(result.unwrap().1);
+        }
+        assert_eq!(pictures.len(), 5);
+        assert_eq!(pts_of(&pictures, FrameType::IDR), [5]);
+    }
+
+    #[test]
+    fn draining_nothing_keeps_the_encoder_open() {
+        let mut encoder = build(threaded_lookahead());
+        assert!(encoder.drain().next().is_none());
+        assert!(encoder.encode(0, frame().image()).is_ok());
+    }
+
+    #[test]
+    fn draining_delayed_frames_finishes_the_encoder() {
+        let mut encoder = build(threaded_lookahead());
+        let frame = frame();
+        for pts in 0..5 {
+            encoder.encode(pts, frame.image()).unwrap();
+        }
+
+        // Nothing is flushed until the drain is used.
+        drop(encoder.drain());
+        encoder.encode(5, frame.image()).unwrap();
+
+        let mut drain = encoder.drain();
+        while let Some(result) = drain.next() {
+            result.unwrap();
+        }
+        assert_eq!(encoder.frames_emitted(), 6);
+        assert_eq!(
+            encoder.encode(6, frame.image()).err(),
+            Some(Error::EncoderFinished)
+        );
+    }
+
+    #[test]
+    fn impossible_reference_counts_are_clamped() {
+        let encoder = build(Setup::default().reference_frames(100));
+        assert_eq!(encoder.reference_frames(), 16);
+    }
+
+    // Encodes one frame forced to be of the given type.
+    fn force(setup: Setup, frame_type: FrameType) -> Result<()> {
+        let mut encoder = build(setup);
+        let frame = frame();
+        let mut image = frame.image();
+        image.set_frame_type(frame_type);
+        encoder.encode(0, image).map(|_| ())
+    }
+
+    #[test]
+    fn bref_needs_a_pyramid() {
+        let setup = || Setup::default().bframes(3);
+        assert_eq!(
+            force(setup().bframe_pyramid(BPyramid::None), FrameType::Bref),
+            Err(Error::IncompatibleFrameType)
+        );
+        assert_eq!(
+            force(setup().bframe_pyramid(BPyramid::Normal), FrameType::Bref),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn bframes_need_bframes_enabled() {
+        let setup = || Setup::default().bframes(0);
+        assert_eq!(
+            force(setup(), FrameType::B),
+            Err(Error::IncompatibleFrameType)
+        );
+        assert_eq!(
+            force(setup().bframe_pyramid(BPyramid::Normal), FrameType::Bref),
+            Err(Error::IncompatibleFrameType)
+        );
+        assert_eq!(force(Setup::default().bframes(3), FrameType::B), Ok(()));
+    }
+
+    #[test]
+    fn sei_payloads_are_written() {
+        // User data unregistered: a UUID, then anything.
+        let mut payload = [0x5a; 16].to_vec();
+        payload.extend_from_slice(b"caption");
+
+        let mut encoder = build(Setup::default());
+        let frame = frame();
+        let mut found = false;
+        let mut check = |data: Data| {
+            for i in 0..data.len() {
+                let unit = data.unit(i);
+                if unit.unit_type() == UnitType::Sei {
+                    let rbsp = unit.rbsp();
+                    found |= rbsp.windows(payload.len()).any(|w| w == &payload[..]);
+                }
+            }
+        };
+
+        for pts in 0..5 {
+            let mut image = frame.image();
+            if pts == 2 {
+                image.add_sei(5, &payload);
+            }
+            if let Some((data, _)) = encoder.encode(pts, image).unwrap() {
+                check(data);
+            }
+        }
+        let mut flush = encoder.flush();
+        while let Some(result) = flush.next() {
+            check(result.unwrap().0);
+        }
+        assert!(found);
+    }
+
+    #[test]
+    fn milliseconds_in_the_default_timebase() {
+        // 25 fps, so a tick is 40ms.
+        let encoder = build(Setup::default().fps(25, 1).vfr_input(false));
+        assert_eq!(encoder.ms_to_pts(1000), 25);
+        assert_eq!(encoder.ms_to_pts(1039), 25);
+        assert_eq!(encoder.pts_to_ms(25), 1000);
+        for pts in [0, 1, 7, 25, 1001] {
+            assert_eq!(encoder.ms_to_pts(encoder.pts_to_ms(pts)), pts);
+        }
+    }
+
+    #[test]
+    fn milliseconds_in_a_custom_timebase() {
+        let encoder = build(Setup::default().timebase(1, 90000).vfr_input(true));
+        assert_eq!(encoder.ms_to_pts(1000), 90000);
+        assert_eq!(encoder.pts_to_ms(90000), 1000);
+        for ms in [0, 1, 33, 1000, 123456] {
+            assert_eq!(encoder.pts_to_ms(encoder.ms_to_pts(ms)), ms);
+        }
+    }
+
+    // Without AQ or mbtree, a frame's average quantizer is the one it was
+    // given.
+    fn exact_qp() -> Setup {
+        Setup::preset(crate::Preset::Medium, crate::Tune::Psnr, false, true)
+    }
+
+    #[test]
+    fn forced_quantizers_are_applied() {
+        let mut encoder = build(exact_qp());
+        let frame = frame();
+
+        for pts in 0..5 {
+            let mut image = frame.image();
+            image.force_qp(30 + pts as i32);
+            let (_, picture) = encoder.encode(pts, image).unwrap().unwrap();
+            assert_eq!(picture.qp(), 30 + pts as i32);
+        }
+    }
+
+    #[test]
+    fn profile_and_level_are_reported() {
+        let encoder = build(Setup::default().high().level(40));
+        assert_eq!(encoder.profile(), Profile::High);
+        assert_eq!(encoder.level(), Level::Level4);
+        assert_eq!(encoder.level_idc(), 40);
+    }
+
+    #[test]
+    fn codec_strings_come_from_the_sps() {
+        // Baseline also sets constraint_set1, since it decodes as main.
+        let mut encoder = build(Setup::default().baseline().level(30));
+        assert_eq!(encoder.codec_string().unwrap(), "avc1.42c01e");
+
+        let mut encoder = build(Setup::default().high().level(40));
+        assert_eq!(encoder.codec_string().unwrap(), "avc1.640028");
+    }
+
+    #[test]
+    fn extradata_holds_the_parameter_sets() {
+        for setup in [Setup::default().baseline(), Setup::default().high()] {
+            let mut encoder = build(setup);
+            let (sps, pps) = encoder.parameter_sets().unwrap();
+            let avcc = encoder.extradata().unwrap();
+
+            assert_eq!(avcc[0], 1);
+            assert_eq!(avcc[1..4], sps[1..4]);
+            assert_eq!(avcc[4], 0xFF);
+            assert_eq!(avcc[5], 0xE1);
+            let sps_len = u16::from_be_bytes([avcc[6], avcc[7]]) as usize;
+            assert_eq!(avcc[8..8 + sps_len], sps[..]);
+
+            let rest = &avcc[8 + sps_len..];
+            assert_eq!(rest[0], 1);
+            let pps_len = u16::from_be_bytes([rest[1], rest[2]]) as usize;
+            assert_eq!(rest[3..3 + pps_len], pps[..]);
+
+            // 8-bit 4:2:0, for the high profile only.
+            let tail: &[u8] = match encoder.profile() {
+                Profile::High => &[0xFD, 0xF8, 0xF8, 0],
+                _ => &[],
+            };
+            assert_eq!(&rest[3 + pps_len..], tail);
+        }
+    }
+
+    #[test]
+    fn unchanged_frames_shrink() {
+        let setup = Setup::preset(crate::Preset::Medium, crate::Tune::None, false, true);
+        let mut encoder = build(setup.screen_content());
+        // Detailed enough that the first frame is expensive.
+        let luma: Vec<u8> = (0..WIDTH * HEIGHT)
+            .map(|i| ((i % WIDTH) * 7 + (i / WIDTH) * 13) as u8)
+            .collect();
+        let frame = gray_to_i420(&luma, WIDTH, HEIGHT);
+
+        let mut sizes = Vec::new();
+        for pts in 0..5 {
+            let result = encoder.encode_if_changed(pts, frame.image(), &frame.image());
+            let (data, _) = result.unwrap().unwrap();
+            sizes.push(data.entirety().len());
+        }
+        for &size in &sizes[1..] {
+            assert!(size * 4 < sizes[0], "{:?}", sizes);
+        }
+    }
+
+    #[test]
+    fn first_frame_qp_applies_after_each_reset() {
+        let mut encoder = build(exact_qp().first_frame_qp(45));
+        let frame = frame();
+        let mut qps = Vec::new();
+
+        for pts in 0..6 {
+            if pts == 3 {
+                encoder.reset().unwrap();
+            }
+            let (_, picture) = encoder.encode(pts, frame.image()).unwrap().unwrap();
+            qps.push(picture.qp());
+        }
+        assert_eq!(qps[0], 45);
+        assert_eq!(qps[3], 45);
+        for &i in &[1, 2, 4, 5] {
+            assert_ne!(qps[i], 45, "{:?}", qps);
+        }
+    }
+
+    // The most frames that come before a frame in decoding order but after it
+    // in display order.
+    fn reorder_depth(pictures: &[Picture]) -> usize {
+        (0..pictures.len())
+            .map(|i| {
+                let pts = pictures[i].pts();
+                pictures[..i].iter().filter(|p| p.pts() > pts).count()
             })
-        })
+            .max()
+            .unwrap_or(0)
+    }
+
+    #[test]
+    fn reordering_is_bounded() {
+        let setup = || Setup::default().adaptive_bframes(3, crate::BAdapt::None);
+        for frames in 0..3 {
+            let pictures = encode_all(build(setup().max_reorder(frames)), 12);
+            assert_eq!(reorder_depth(&pictures), frames as usize);
+        }
     }
 }
@@ -1,8 +1,89 @@
-use core::result;
+use core::{fmt, result};
 
 #[derive(Copy, Clone, Hash, Debug, PartialEq, Eq)]
+#[non_exhaustive]
 /// Indicates that something bad happened.
-pub struct Error;
+pub enum Error {
+    /// x264 refused to open an encoder with the given parameters.
+    Open,
+    /// x264 failed to encode a frame or the headers.
+    Encode,
+    /// The frame type forced on an image can't be produced with the encoder's
+    /// settings.
+    IncompatibleFrameType,
+    /// The width or height doesn't suit the colorspace.
+    InvalidDimensions,
+    /// The framerate has a zero numerator or denominator.
+    InvalidFramerate,
+    /// The minimum keyframe interval is greater than the maximum, or less than
+    /// one where it can't mean automatic.
+    InvalidKeyframeInterval,
+    /// A VBV maximum rate was set without a buffer size.
+    InvalidVbv,
+    /// The bframe settings are inconsistent, such as adaptive bframes without
+    /// any bframes.
+    InvalidBframes,
+    /// There are more reference frames than the level allows at the video's
+    /// size.
+    TooManyReferenceFrames,
+    /// x264 refused to reconfigure the encoder.
+    Reconfigure,
+    /// The encoder was drained with threaded lookahead, so it can't be fed
+    /// any more frames.
+    EncoderFinished,
+    /// x264 doesn't know an option, or can't parse its value.
+    InvalidOption,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Error::Open => "x264 could not open an encoder with these parameters",
+            Error::Encode => "x264 failed to encode",
+            Error::IncompatibleFrameType => {
+                "the forced frame type is incompatible with the encoder's settings"
+            }
+            Error::InvalidDimensions => "the dimensions don't suit the colorspace's subsampling",
+            Error::InvalidFramerate => "the framerate must be nonzero",
+            Error::InvalidKeyframeInterval => "the minimum keyframe interval is out of range",
+            Error::InvalidVbv => "a VBV maximum rate requires a buffer size",
+            Error::InvalidBframes => "the bframe settings are inconsistent",
+            Error::TooManyReferenceFrames => "the level doesn't allow that many reference frames",
+            Error::Reconfigure => "x264 could not reconfigure the encoder",
+            Error::EncoderFinished => "the encoder has finished and can't take more frames",
+            Error::InvalidOption => "x264 could not parse an option",
+        })
+    }
+}
+
+#[derive(Copy, Clone, Hash, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+/// Why planes don't make a valid image.
+pub enum ImageError {
+    /// The number of planes doesn't match the colorspace.
+    WrongPlaneCount,
+    /// The width or height isn't a multiple of the colorspace's subsampling.
+    NonMultipleDimensions,
+    /// The stride of the plane with this index is too small for the width.
+    StrideTooSmall(usize),
+    /// The plane with this index has too few rows for the height.
+    InsufficientRows(usize),
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ImageError::WrongPlaneCount => {
+                f.write_str("the number of planes doesn't match the colorspace")
+            }
+            ImageError::NonMultipleDimensions => {
+                f.write_str("the dimensions don't suit the colorspace's subsampling")
+            }
+            ImageError::StrideTooSmall(i) => write!(f, "plane {}'s stride is too small", i),
+            ImageError::InsufficientRows(i) => write!(f, "plane {} has too few rows", i),
+        }
+    }
+}
 
 /// A specialized Result type for video encoding operations.
 pub type Result<T> = result::Result<T, Error>;
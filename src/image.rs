@@ -1,14 +1,21 @@
-use crate::{Colorspace, Encoding, Modifier};
+use crate::{sei, Colorspace, Encoding, ImageError, Modifier};
+use alloc::vec::Vec;
 use core::marker::PhantomData;
-use core::ptr;
+use core::{ptr, slice};
 use x264::*;
 
+#[derive(Clone)]
 /// Input image data to be given to the encoder.
 pub struct Image<'a> {
     raw: x264_image_t,
     width: i32,
     height: i32,
     frame_type: FrameType,
+    pic_struct: PicStruct,
+    full_range: bool,
+    qp: Option<i32>,
+    sei: Vec<(i32, &'a [u8])>,
+    mb_info: Vec<u8>,
     spooky: PhantomData<&'a [u8]>,
 }
 
@@ -17,7 +24,7 @@ impl<'a> Image<'a> {
     ///
     /// # Panics
     ///
-    /// Panics if the plane is invalid.
+    /// Panics if the plane is invalid, as `try_new` would report.
     pub fn new<E: Into<Encoding>>(
         format: E,
         width: i32,
@@ -25,45 +32,55 @@ impl<'a> Image<'a> {
         frame_type: Option<FrameType>,
         planes: &[Plane<'a>],
     ) -> Self {
-        //TODO: Get someone who knows what they're doing to verify this.
-
-        use self::Colorspace::*;
+        match Self::try_new(format, width, height, frame_type, planes) {
+            Ok(image) => image,
+            Err(err) => panic!("invalid image: {}", err),
+        }
+    }
 
+    /// Makes a new image with the given information, or explains why the
+    /// planes don't suit it.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the number of planes doesn't match the colorspace, the width
+    /// or height doesn't suit its subsampling, or a plane's stride or length
+    /// is too small.
+    pub fn try_new<E: Into<Encoding>>(
+        format: E,
+        width: i32,
+        height: i32,
+        frame_type: Option<FrameType>,
+        planes: &[Plane<'a>],
+    ) -> Result<Self, ImageError> {
         let format = format.into();
+        let (pc, wm, hm, _, _) = format.colorspace().layout();
 
-        let (pc, wm, hm, ws, hs): (_, _, _, &[_], &[_]) = match format.colorspace() {
-            I420 | YV12 => (3, 2, 2, &[2, 1, 1], &[2, 1, 1]),
-            NV12 | NV21 => (2, 2, 2, &[2, 2], &[2, 1]),
-            I422 | YV16 => (3, 2, 1, &[2, 1, 1], &[1, 1, 1]),
-            NV16 => (2, 2, 1, &[2, 2], &[1, 1]),
-            #[cfg(yuyv)]
-            YUYV | UYVY => (1, 1, 1, &[2], &[1]),
-            V210 => (1, 1, 1, &[4], &[1]),
-            I444 | YV24 => (3, 1, 1, &[1, 1, 1], &[1, 1, 1]),
-            BGR | RGB => (1, 1, 1, &[3], &[1]),
-            BGRA => (1, 1, 1, &[4], &[1]),
-        };
-
-        let (wq, wr) = (width / wm, width % wm);
-        let (hq, hr) = (height / hm, height % hm);
-        let depth = if format.has_modifier(Modifier::HighDepth) {
-            2
-        } else {
-            1
-        };
-
-        // Check that the number of planes matches pc.
-        assert!(planes.len() == pc);
-        // Check that the width and the height are multiples of wm and hm.
-        assert!(wr == 0 && hr == 0);
+        if planes.len() != pc {
+            return Err(ImageError::WrongPlaneCount);
+        }
+        if width % wm != 0 || height % hm != 0 {
+            return Err(ImageError::NonMultipleDimensions);
+        }
         for (i, plane) in planes.iter().enumerate() {
-            // Check that the plane's stride is at least depth * wq * ws[i].
-            assert!(depth * wq * ws[i] <= plane.stride);
-            // Check that there are at least hq * hs[i] rows in the plane.
-            assert!(hq * hs[i] <= plane.data.len() as i32 / plane.stride);
+            let layout = format.plane(i, width, height);
+            if plane.stride < layout.stride {
+                return Err(ImageError::StrideTooSmall(i));
+            }
+            if plane.data.len() < plane.stride as usize * layout.rows as usize {
+                return Err(ImageError::InsufficientRows(i));
+            }
         }
 
-        unsafe { Self::new_unchecked(format, width, height, frame_type.unwrap_or(FrameType::Auto), planes) }
+        Ok(unsafe {
+            Self::new_unchecked(
+                format,
+                width,
+                height,
+                frame_type.unwrap_or(FrameType::Auto),
+                planes,
+            )
+        })
     }
 
     /// Makes a new packed BGR image.
@@ -93,6 +110,104 @@ impl<'a> Image<'a> {
         Self::new(Colorspace::BGRA, width, height, None, &[plane])
     }
 
+    /// Makes a new NV21 image from a contiguous buffer holding the Y plane
+    /// followed by the interleaved VU plane, as Android cameras produce.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the buffer is too small or the dimensions are odd.
+    pub fn nv21(width: i32, height: i32, data: &'a [u8]) -> Self {
+        Self::contiguous(Colorspace::NV21, width, height, data)
+    }
+
+    /// Makes a new YV12 image from a contiguous buffer holding the Y plane,
+    /// then the 2x2 subsampled V plane, then the U plane.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the buffer is too small or the dimensions are odd.
+    pub fn yv12(width: i32, height: i32, data: &'a [u8]) -> Self {
+        Self::contiguous(Colorspace::YV12, width, height, data)
+    }
+
+    /// Makes a new YV16 image from a contiguous buffer holding the Y plane,
+    /// then the 2x1 subsampled V plane, then the U plane.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the buffer is too small or the width is odd.
+    pub fn yv16(width: i32, height: i32, data: &'a [u8]) -> Self {
+        Self::contiguous(Colorspace::YV16, width, height, data)
+    }
+
+    /// Makes a new YV24 image from a contiguous buffer holding the Y plane,
+    /// then the V plane, then the U plane.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the buffer is too small.
+    pub fn yv24(width: i32, height: i32, data: &'a [u8]) -> Self {
+        Self::contiguous(Colorspace::YV24, width, height, data)
+    }
+
+    // Splits a buffer of tightly packed planes, in the colorspace's order.
+    fn contiguous(format: Colorspace, width: i32, height: i32, data: &'a [u8]) -> Self {
+        let (pc, _, _, _, _) = format.layout();
+        let mut planes = Vec::with_capacity(pc);
+        let mut rest = data;
+
+        for i in 0..pc {
+            let layout = Encoding::from(format).plane(i, width, height);
+            let (plane, tail) = rest.split_at(layout.size());
+            planes.push(Plane {
+                stride: layout.stride,
+                data: plane,
+            });
+            rest = tail;
+        }
+
+        Self::new(format, width, height, None, &planes)
+    }
+
+    /// Makes a new packed YUYV image.
+    ///
+    /// Only available when the linked x264 supports it.
+    #[cfg(yuyv)]
+    pub fn yuyv(width: i32, height: i32, data: &'a [u8]) -> Self {
+        let plane = Plane {
+            stride: data.len() as i32 / height,
+            data,
+        };
+        Self::new(Colorspace::YUYV, width, height, None, &[plane])
+    }
+
+    /// Makes a new packed UYVY image.
+    ///
+    /// Only available when the linked x264 supports it.
+    #[cfg(yuyv)]
+    pub fn uyvy(width: i32, height: i32, data: &'a [u8]) -> Self {
+        let plane = Plane {
+            stride: data.len() as i32 / height,
+            data,
+        };
+        Self::new(Colorspace::UYVY, width, height, None, &[plane])
+    }
+
+    /// Makes a new packed V210 image.
+    ///
+    /// Each row holds groups of 48 pixels in 128 bytes, so the row length is
+    /// rounded up to a multiple of 128 bytes. The image carries the
+    /// `HighDepth` modifier, so the encoder must be built with the same
+    /// encoding and a bit depth of 10.
+    pub fn v210(width: i32, height: i32, data: &'a [u8]) -> Self {
+        let plane = Plane {
+            stride: data.len() as i32 / height,
+            data,
+        };
+        let format = Encoding::from(Colorspace::V210).add_modifier(Modifier::HighDepth);
+        Self::new(format, width, height, None, &[plane])
+    }
+
     /// Set the frame type you would like the encoder to create for only this image. Set this to [`FrameType::IDR`] for a gop "refresh".
     pub fn set_frame_type(&mut self, frame_type: FrameType) {
         self.frame_type = frame_type;
@@ -103,6 +218,52 @@ impl<'a> Image<'a> {
         &self.frame_type
     }
 
+    /// Set how this image should be displayed, which is signalled through
+    /// picture timing SEI.
+    pub fn set_pic_struct(&mut self, pic_struct: PicStruct) {
+        self.pic_struct = pic_struct;
+    }
+
+    /// Get the picture structure
+    pub fn pic_struct(&self) -> PicStruct {
+        self.pic_struct
+    }
+
+    /// Marks this image's samples as using the full range of values (as with
+    /// JPEG), rather than the limited range. Defaults to `false`.
+    ///
+    /// This doesn't change how the image is encoded, but debug builds assert
+    /// that the encoder signals full range (see `Setup::full_range`), since
+    /// the video would otherwise play back washed out.
+    pub fn set_full_range(&mut self, full_range: bool) {
+        self.full_range = full_range;
+    }
+
+    /// Whether the image is marked as full range
+    pub fn full_range(&self) -> bool {
+        self.full_range
+    }
+
+    /// Forces the quantizer for this image's frame, overriding x264's rate
+    /// control (e.g. to implement your own).
+    pub fn force_qp(&mut self, qp: i32) {
+        self.qp = Some(qp);
+    }
+
+    /// Get the forced quantizer, if any
+    pub fn forced_qp(&self) -> Option<i32> {
+        self.qp
+    }
+
+    /// Attaches an SEI message of the given payload type to this image's
+    /// frame, such as user data carrying closed captions.
+    ///
+    /// The payload is copied when the image is encoded, and written once the
+    /// frame leaves the lookahead.
+    pub fn add_sei(&mut self, payload_type: i32, data: &'a [u8]) {
+        self.sei.push((payload_type, data));
+    }
+
     /// Makes a new image with the given planes and colorspace.
     ///
     /// # Safety
@@ -118,25 +279,69 @@ impl<'a> Image<'a> {
         planes: &[Plane<'a>],
     ) -> Self {
         let mut strides = [0; 4];
-        let mut pointers = [ptr::null_mut(); 4];
+        let mut pointers = [ptr::null(); 4];
 
         for (i, &Plane { stride, data }) in planes.iter().enumerate() {
             strides[i] = stride;
-            pointers[i] = data.as_ptr() as *mut u8;
+            pointers[i] = data.as_ptr();
         }
 
+        let mut image = unsafe {
+            Self::from_raw_planes(
+                format,
+                width,
+                height,
+                pointers,
+                strides,
+                planes.len() as i32,
+            )
+        };
+        image.frame_type = frame_type;
+        image
+    }
+
+    /// Makes a new image from raw plane pointers, such as memory mapped from
+    /// a capture device or GPU, without needing to know their lengths.
+    ///
+    /// # Safety
+    ///
+    /// For each of the first `plane_count` planes, the pointer must be valid
+    /// for reads of as many rows of its stride as `Encoding::plane_layout`
+    /// gives, and the stride must be at least the minimum it gives. The
+    /// memory must not be written to for the lifetime `'a`, which the caller
+    /// chooses and must not outlive the memory. `plane_count` must be the
+    /// number of planes the encoding has, and the width and height must suit
+    /// its subsampling.
+    pub unsafe fn from_raw_planes(
+        format: Encoding,
+        width: i32,
+        height: i32,
+        pointers: [*const u8; 4],
+        strides: [i32; 4],
+        plane_count: i32,
+    ) -> Self {
         let raw = x264_image_t {
             i_csp: format.into_raw(),
-            i_plane: planes.len() as i32,
+            i_plane: plane_count,
             i_stride: strides,
-            plane: pointers,
+            plane: [
+                pointers[0] as *mut u8,
+                pointers[1] as *mut u8,
+                pointers[2] as *mut u8,
+                pointers[3] as *mut u8,
+            ],
         };
 
         Self {
             raw,
             width,
             height,
-            frame_type,
+            frame_type: FrameType::Auto,
+            pic_struct: PicStruct::Auto,
+            full_range: false,
+            qp: None,
+            sei: Vec::new(),
+            mb_info: Vec::new(),
             spooky: PhantomData,
         }
     }
@@ -160,6 +365,76 @@ impl<'a> Image<'a> {
     pub fn raw(&self) -> x264_image_t {
         self.raw
     }
+
+    // Whether every plane's stride is at least the encoding's minimum, which
+    // only the checked constructors guarantee.
+    pub(crate) fn strides_fit(&self) -> bool {
+        let format = self.encoding();
+        (0..self.raw.i_plane as usize)
+            .all(|i| self.raw.i_stride[i] >= format.plane(i, self.width, self.height).stride)
+    }
+
+    pub(crate) fn raw_sei(&self) -> x264_sei_t {
+        sei::to_raw(&self.sei)
+    }
+
+    // Copies the macroblock info into memory that x264 frees with `sei::free`.
+    pub(crate) fn raw_mb_info(&self) -> *mut u8 {
+        if self.mb_info.is_empty() {
+            return ptr::null_mut();
+        }
+        unsafe {
+            let raw = sei::allocate(self.mb_info.len());
+            ptr::copy_nonoverlapping(self.mb_info.as_ptr(), raw, self.mb_info.len());
+            raw
+        }
+    }
+
+    // Flags each macroblock that is identical in `prev` as constant, if the
+    // images can be compared.
+    pub(crate) fn mark_unchanged(&mut self, prev: &Image) {
+        let format = self.encoding();
+        if format != prev.encoding()
+            || (self.width, self.height) != (prev.width, prev.height)
+            || format.colorspace() == Colorspace::V210
+        {
+            return;
+        }
+
+        let flip = format.has_modifier(Modifier::VerticalFlip);
+        let mb_width = ((self.width + 15) / 16) as usize;
+        let mb_height = ((self.height + 15) / 16) as usize;
+        let mut flags = alloc::vec![X264_MBINFO_CONSTANT as u8; mb_width * mb_height];
+
+        for i in 0..self.raw.i_plane as usize {
+            let layout = format.plane(i, self.width, self.height);
+            // The bytes and rows of this plane that each macroblock covers.
+            let mb_bytes = (layout.stride * 16 / self.width) as usize;
+            let mb_rows = layout.rows * 16 / self.height;
+
+            for y in 0..layout.rows {
+                let (a, b) = unsafe {
+                    let a = self.raw.plane[i].add((y * self.raw.i_stride[i]) as usize);
+                    let b = prev.raw.plane[i].add((y * prev.raw.i_stride[i]) as usize);
+                    let len = layout.stride as usize;
+                    (slice::from_raw_parts(a, len), slice::from_raw_parts(b, len))
+                };
+                let row = if flip { layout.rows - 1 - y } else { y };
+                let flags = &mut flags[(row / mb_rows) as usize * mb_width..];
+
+                for (flag, (a, b)) in flags
+                    .iter_mut()
+                    .zip(a.chunks(mb_bytes).zip(b.chunks(mb_bytes)))
+                {
+                    if a != b {
+                        *flag = 0;
+                    }
+                }
+            }
+        }
+
+        self.mb_info = flags;
+    }
 }
 
 /// A single plane of an image.
@@ -171,7 +446,7 @@ pub struct Plane<'a> {
 }
 
 /// List of possible frame type values
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Hash, Debug, Eq, PartialEq)]
 pub enum FrameType {
     /// Allow the encoder to choose the best frame type
     Auto = X264_TYPE_AUTO as isize,
@@ -181,8 +456,305 @@ pub enum FrameType {
     I = X264_TYPE_I as isize,
     /// Predicted Frame
     P = X264_TYPE_P as isize,
+    /// Bi-Directional Frame that other bframes may reference. Forcing this
+    /// requires a `BPyramid` other than `BPyramid::None`; use `B` for a frame
+    /// that nothing else references.
     Bref = X264_TYPE_BREF as isize,
     /// Bi-Directional Frame
     B = X264_TYPE_B as isize,
+    /// A keyframe, which is an IDR frame unless open GOP is enabled
     Keyframe = X264_TYPE_KEYFRAME as isize,
-}
\ No newline at end of file
+}
+
+impl FrameType {
+    #[doc(hidden)]
+    pub fn from_raw(raw: i32) -> Self {
+        match raw as u32 {
+            X264_TYPE_IDR => FrameType::IDR,
+            X264_TYPE_I => FrameType::I,
+            X264_TYPE_P => FrameType::P,
+            X264_TYPE_BREF => FrameType::Bref,
+            X264_TYPE_B => FrameType::B,
+            X264_TYPE_KEYFRAME => FrameType::Keyframe,
+            _ => FrameType::Auto,
+        }
+    }
+}
+
+/// How a picture should be displayed, in terms of fields.
+#[derive(Clone, Copy, Hash, Debug, Eq, PartialEq)]
+pub enum PicStruct {
+    /// Let the encoder decide
+    Auto = 0,
+    /// A progressive frame
+    Progressive = 1,
+    /// Top field followed by bottom field
+    TopBottom = 4,
+    /// Bottom field followed by top field
+    BottomTop = 5,
+    /// Top field, bottom field, then the top field repeated
+    TopBottomTop = 6,
+    /// Bottom field, top field, then the bottom field repeated
+    BottomTopBottom = 7,
+    /// A progressive frame displayed twice
+    Double = 8,
+    /// A progressive frame displayed three times
+    Triple = 9,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const Y: [u8; 16] = [0; 16];
+    const UV: [u8; 4] = [0; 4];
+
+    // A 4x4 I420 image with the given planes.
+    fn i420(planes: &[Plane]) -> Result<(), ImageError> {
+        Image::try_new(Colorspace::I420, 4, 4, None, planes).map(|_| ())
+    }
+
+    fn plane(stride: i32, data: &[u8]) -> Plane {
+        Plane { stride, data }
+    }
+
+    #[test]
+    fn valid_planes_make_an_image() {
+        assert_eq!(i420(&[plane(4, &Y), plane(2, &UV), plane(2, &UV)]), Ok(()));
+    }
+
+    #[test]
+    fn wrong_plane_count() {
+        assert_eq!(
+            i420(&[plane(4, &Y), plane(2, &UV)]),
+            Err(ImageError::WrongPlaneCount)
+        );
+    }
+
+    #[test]
+    fn non_multiple_dimensions() {
+        let planes = [plane(4, &Y), plane(2, &UV), plane(2, &UV)];
+        assert_eq!(
+            Image::try_new(Colorspace::I420, 3, 4, None, &planes).map(|_| ()),
+            Err(ImageError::NonMultipleDimensions)
+        );
+    }
+
+    #[test]
+    fn stride_too_small() {
+        assert_eq!(
+            i420(&[plane(4, &Y), plane(2, &UV), plane(1, &UV)]),
+            Err(ImageError::StrideTooSmall(2))
+        );
+    }
+
+    #[test]
+    fn insufficient_rows() {
+        assert_eq!(
+            i420(&[plane(4, &Y[..12]), plane(2, &UV), plane(2, &UV)]),
+            Err(ImageError::InsufficientRows(0))
+        );
+    }
+
+    #[test]
+    fn v210_rows_are_padded() {
+        // 64 pixels take two groups of 48, so 256 bytes.
+        let data = alloc::vec![0; 256 * 2];
+        let image = Image::v210(64, 2, &data);
+        let encoding = image.encoding();
+        assert_eq!(encoding.colorspace(), Colorspace::V210);
+        assert!(encoding.has_modifier(Modifier::HighDepth));
+        assert_eq!(image.raw.i_plane, 1);
+        assert_eq!(image.raw.i_stride[0], 256);
+        assert_eq!(encoding.plane_layout(64, 2)[0].stride, 256);
+    }
+
+    #[test]
+    #[should_panic]
+    fn v210_rows_must_be_padded() {
+        // 64 pixels packed without padding don't fill a whole group.
+        let data = alloc::vec![0; 171 * 2];
+        Image::v210(64, 2, &data);
+    }
+
+    #[test]
+    fn v210_encodes_at_10_bits() {
+        let (width, height) = (64, 64);
+        let encoding = Encoding::with_modifier(Colorspace::V210, Modifier::HighDepth);
+        let mut encoder = crate::Setup::default()
+            .bit_depth(10)
+            .build(encoding, width, height)
+            .unwrap();
+
+        // Mid-gray: three 10-bit components of 512 in each little-endian word.
+        let word = 512u32 | 512 << 10 | 512 << 20;
+        let data: Vec<u8> = word
+            .to_le_bytes()
+            .iter()
+            .cycle()
+            .take(256 * height as usize)
+            .cloned()
+            .collect();
+        for pts in 0..3 {
+            encoder
+                .encode(pts, Image::v210(width, height, &data))
+                .unwrap();
+        }
+        let mut flush = encoder.flush();
+        while let Some(result) = flush.next() {
+            result.unwrap();
+        }
+        assert_eq!(flush.encoder().frames_emitted(), 3);
+    }
+
+    type Constructor = for<'a> fn(i32, i32, &'a [u8]) -> Image<'a>;
+
+    fn constructors() -> Vec<(Colorspace, Constructor)> {
+        #[cfg_attr(not(yuyv), allow(unused_mut))]
+        let mut constructors: Vec<(Colorspace, Constructor)> = alloc::vec![
+            (Colorspace::NV21, |w, h, data| Image::nv21(w, h, data)),
+            (Colorspace::YV12, |w, h, data| Image::yv12(w, h, data)),
+            (Colorspace::YV16, |w, h, data| Image::yv16(w, h, data)),
+            (Colorspace::YV24, |w, h, data| Image::yv24(w, h, data)),
+        ];
+        #[cfg(yuyv)]
+        constructors.extend_from_slice(&[
+            (Colorspace::YUYV, |w, h, data| Image::yuyv(w, h, data)),
+            (Colorspace::UYVY, |w, h, data| Image::uyvy(w, h, data)),
+        ]);
+        constructors
+    }
+
+    #[test]
+    fn constructors_match_the_plane_layout() {
+        let (width, height) = (64, 32);
+
+        for (csp, constructor) in constructors() {
+            let layout = Encoding::from(csp).plane_layout(width, height);
+            let size = layout.iter().map(crate::PlaneLayout::size).sum();
+            let data = alloc::vec![0; size];
+            let image = constructor(width, height, &data);
+
+            assert_eq!(image.encoding(), Encoding::from(csp));
+            assert_eq!(image.raw.i_plane as usize, layout.len(), "{:?}", csp);
+            let mut offset = 0;
+            for (i, plane) in layout.iter().enumerate() {
+                assert_eq!(image.raw.i_stride[i], plane.stride, "{:?} plane {}", csp, i);
+                assert_eq!(
+                    image.raw.plane[i] as usize,
+                    data.as_ptr() as usize + offset,
+                    "{:?} plane {}",
+                    csp,
+                    i
+                );
+                offset += plane.size();
+            }
+        }
+    }
+
+    #[test]
+    fn constructors_encode() {
+        let (width, height) = (64, 32);
+
+        for (csp, constructor) in constructors() {
+            let encoding = Encoding::from(csp);
+            if !encoding.is_supported() {
+                continue;
+            }
+            let size = encoding
+                .plane_layout(width, height)
+                .iter()
+                .map(crate::PlaneLayout::size)
+                .sum();
+            let data = alloc::vec![128; size];
+            let mut encoder = crate::Setup::default().build(csp, width, height).unwrap();
+            for pts in 0..2 {
+                encoder
+                    .encode(pts, constructor(width, height, &data))
+                    .unwrap();
+            }
+            let mut flush = encoder.flush();
+            while let Some(result) = flush.next() {
+                result.unwrap();
+            }
+            assert_eq!(flush.encoder().frames_emitted(), 2, "{:?}", csp);
+        }
+    }
+
+    // An image with its planes one after another, at the minimum strides.
+    fn contiguous_image(format: Encoding, width: i32, height: i32, data: &[u8]) -> Image {
+        let mut planes = Vec::new();
+        let mut rest = data;
+        for i in 0..format.colorspace().layout().0 {
+            let layout = format.plane(i, width, height);
+            let (data, next) = rest.split_at(layout.size());
+            planes.push(plane(layout.stride, data));
+            rest = next;
+        }
+        Image::new(format, width, height, None, &planes)
+    }
+
+    // The macroblocks that `mark_unchanged` finds changed, after setting the
+    // given bytes of each plane.
+    fn changed_macroblocks(
+        format: Encoding,
+        width: i32,
+        height: i32,
+        changes: &[(usize, i32, i32)],
+    ) -> Vec<usize> {
+        let size = format
+            .plane_layout(width, height)
+            .iter()
+            .map(|plane| plane.size())
+            .sum();
+        let prev = alloc::vec![0; size];
+        let mut data = prev.clone();
+
+        for &(i, x, y) in changes {
+            let offset: usize = (0..i).map(|i| format.plane(i, width, height).size()).sum();
+            let stride = format.plane(i, width, height).stride;
+            data[offset + (y * stride + x) as usize] = 1;
+        }
+
+        let prev = contiguous_image(format, width, height, &prev);
+        let mut image = contiguous_image(format, width, height, &data);
+        image.mark_unchanged(&prev);
+
+        let mbs = ((width + 15) / 16 * ((height + 15) / 16)) as usize;
+        assert_eq!(image.mb_info.len(), mbs);
+        (0..mbs)
+            .filter(|&mb| image.mb_info[mb] != X264_MBINFO_CONSTANT as u8)
+            .collect()
+    }
+
+    #[test]
+    fn changed_macroblocks_are_found() {
+        // 4x2 macroblocks, with a luma change in the second row and a chroma
+        // change at the end of the first.
+        let format = Encoding::from(Colorspace::I420);
+        assert_eq!(changed_macroblocks(format, 64, 32, &[]), []);
+        assert_eq!(changed_macroblocks(format, 64, 32, &[(0, 20, 20)]), [5]);
+        assert_eq!(
+            changed_macroblocks(format, 64, 32, &[(0, 20, 20), (1, 25, 2)]),
+            [3, 5]
+        );
+    }
+
+    #[test]
+    fn flipped_images_are_marked_upright() {
+        // The last row in memory is the first in the picture.
+        let format = Encoding::from(Colorspace::I420).add_modifier(Modifier::VerticalFlip);
+        assert_eq!(changed_macroblocks(format, 64, 32, &[(0, 20, 20)]), [1]);
+        assert_eq!(changed_macroblocks(format, 64, 32, &[(2, 0, 15)]), [0]);
+    }
+
+    #[test]
+    fn partial_macroblocks_are_marked() {
+        // 3x2 macroblocks, the last column and row of which are partial.
+        let format = Encoding::from(Colorspace::I420);
+        assert_eq!(changed_macroblocks(format, 40, 24, &[(0, 39, 23)]), [5]);
+        assert_eq!(changed_macroblocks(format, 40, 24, &[(0, 32, 0)]), [2]);
+        assert_eq!(changed_macroblocks(format, 40, 24, &[(1, 19, 11)]), [5]);
+        assert_eq!(changed_macroblocks(format, 40, 24, &[(0, 15, 15)]), [0]);
+    }
+}
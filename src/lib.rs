@@ -8,22 +8,34 @@
 // TODO: clippy 1.68
 // #![deny(clippy::multiple_unsafe_ops_per_block)]
 
+extern crate alloc;
+#[cfg(feature = "bytes")]
+extern crate bytes;
 extern crate x264_sys;
 
 use x264_sys::x264;
 
+mod alpha;
 mod colorspace;
+mod convert;
 mod data;
 mod encoder;
 mod error;
 mod image;
+mod multi;
 mod picture;
+mod sei;
 mod setup;
+mod stats;
 
+pub use alpha::*;
 pub use colorspace::*;
+pub use convert::*;
 pub use data::*;
 pub use encoder::*;
 pub use error::*;
 pub use image::*;
+pub use multi::*;
 pub use picture::*;
 pub use setup::*;
+pub use stats::*;
@@ -0,0 +1,63 @@
+use crate::{Data, Encoder, Image, Picture, Result};
+use alloc::vec::Vec;
+
+/// Several encoders that are fed the same frames, such as the renditions of
+/// an adaptive bitrate ladder.
+///
+/// Every encoder must take the same images, so the renditions can differ in
+/// bitrate or any other setting, but not in size or encoding.
+pub struct MultiEncoder {
+    encoders: Vec<Encoder>,
+}
+
+impl MultiEncoder {
+    /// Combines the encoders, in the order their output is returned.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are no encoders, or if they don't all have the same
+    /// width, height and encoding.
+    pub fn new(encoders: Vec<Encoder>) -> Self {
+        let first = encoders.first().expect("no encoders");
+        for encoder in &encoders[1..] {
+            assert_eq!(encoder.width(), first.width());
+            assert_eq!(encoder.height(), first.height());
+            assert_eq!(encoder.encoding(), first.encoding());
+        }
+        Self { encoders }
+    }
+
+    /// Feeds a frame to every encoder, returning the output of each, in
+    /// order.
+    ///
+    /// # Errors
+    ///
+    /// Fails as soon as any encoder fails, as with `Encoder::encode`, in which
+    /// case the frame was fed to some encoders but not others.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is a mismatch between the image and the encoders, as
+    /// with `Encoder::encode`.
+    pub fn encode(&mut self, pts: i64, image: Image) -> Result<Vec<Option<(Data, Picture)>>> {
+        self.encoders
+            .iter_mut()
+            .map(|encoder| encoder.encode(pts, image.clone()))
+            .collect()
+    }
+
+    /// The encoders, in order.
+    pub fn encoders(&self) -> &[Encoder] {
+        &self.encoders
+    }
+
+    /// The encoders, in order, e.g. to get their headers.
+    pub fn encoders_mut(&mut self) -> &mut [Encoder] {
+        &mut self.encoders
+    }
+
+    /// Splits this back into its encoders, e.g. to flush each of them.
+    pub fn into_encoders(self) -> Vec<Encoder> {
+        self.encoders
+    }
+}
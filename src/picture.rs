@@ -1,8 +1,12 @@
+use crate::FrameType;
 use x264::*;
 
 /// Output information about an encoded frame.
 pub struct Picture {
     raw: x264_picture_t,
+    psnr: bool,
+    ssim: bool,
+    gop_position: u64,
 }
 
 impl Picture {
@@ -11,6 +15,54 @@ impl Picture {
         self.raw.b_keyframe != 0
     }
 
+    /// The type the frame was encoded as.
+    pub fn frame_type(&self) -> FrameType {
+        FrameType::from_raw(self.raw.i_type)
+    }
+
+    /// Whether decoding can start at this picture without any earlier data,
+    /// given the headers.
+    ///
+    /// With an open GOP, keyframes may be I-frames whose following B-frames
+    /// still reference earlier pictures, so only IDR keyframes qualify.
+    pub fn is_random_access_point(&self) -> bool {
+        self.keyframe() && self.frame_type() == FrameType::IDR
+    }
+
+    /// Whether the frame carries a recovery point SEI, which tells decoders
+    /// joining mid-stream when they can start displaying.
+    ///
+    /// x264 writes one on every keyframe that isn't IDR: the I-frames of an
+    /// open GOP, and the first frame of each intra refresh cycle (see
+    /// `Setup::intra_refresh_period`), whose picture is complete once the
+    /// cycle is.
+    pub fn is_recovery_point(&self) -> bool {
+        self.keyframe() && self.frame_type() != FrameType::IDR
+    }
+
+    /// Whether other frames may reference this one.
+    ///
+    /// Only plain bframes are never referenced, so they can be dropped (e.g.
+    /// by a selective forwarding unit) without breaking any other frame.
+    pub fn is_reference(&self) -> bool {
+        self.frame_type() != FrameType::B
+    }
+
+    /// The number of pictures returned since the last keyframe, which is 0
+    /// for a keyframe itself.
+    ///
+    /// This counts in decoding order, which is the order pictures are
+    /// returned in, so it is how far a decoder jumping to the keyframe (e.g.
+    /// for trick play) has to decode to reach this picture.
+    pub fn gop_position(&self) -> u64 {
+        self.gop_position
+    }
+
+    /// The average quantizer the frame was encoded with.
+    pub fn qp(&self) -> i32 {
+        self.raw.i_qpplus1 - 1
+    }
+
     /// The presentation timestamp.
     pub fn pts(&self) -> i64 {
         self.raw.i_pts
@@ -21,8 +73,47 @@ impl Picture {
         self.raw.i_dts
     }
 
+    /// The frame's PSNR for each plane (Y, U then V), in decibels.
+    ///
+    /// Returns `None` unless the encoder was built with `Setup::psnr`.
+    pub fn psnr(&self) -> Option<[f64; 3]> {
+        if self.psnr {
+            Some(self.raw.prop.f_psnr)
+        } else {
+            None
+        }
+    }
+
+    /// The frame's SSIM.
+    ///
+    /// Returns `None` unless the encoder was built with `Setup::ssim`.
+    pub fn ssim(&self) -> Option<f64> {
+        if self.ssim {
+            Some(self.raw.prop.f_ssim)
+        } else {
+            None
+        }
+    }
+
     #[doc(hidden)]
     pub unsafe fn from_raw(raw: x264_picture_t) -> Self {
-        Self { raw }
+        Self {
+            raw,
+            psnr: false,
+            ssim: false,
+            gop_position: 0,
+        }
+    }
+
+    // x264 only fills in the metrics that were enabled.
+    pub(crate) fn with_metrics(mut self, psnr: bool, ssim: bool) -> Self {
+        self.psnr = psnr;
+        self.ssim = ssim;
+        self
+    }
+
+    pub(crate) fn with_gop_position(mut self, position: u64) -> Self {
+        self.gop_position = position;
+        self
     }
 }
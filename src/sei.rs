@@ -0,0 +1,79 @@
+use alloc::alloc::{alloc, dealloc, handle_alloc_error, Layout};
+use core::ffi::c_void;
+use core::{mem, ptr};
+use x264::*;
+
+// Every allocation handed to x264 is prefixed with its size, so that `free`
+// can rebuild the layout from the pointer alone.
+const HEADER: usize = 16;
+
+pub unsafe fn allocate(size: usize) -> *mut u8 {
+    let layout = Layout::from_size_align(size + HEADER, HEADER).unwrap();
+    let base = unsafe { alloc(layout) };
+    if base.is_null() {
+        handle_alloc_error(layout);
+    }
+    unsafe {
+        (base as *mut usize).write(size);
+        base.add(HEADER)
+    }
+}
+
+pub unsafe extern "C" fn free(ptr: *mut c_void) {
+    if ptr.is_null() {
+        return;
+    }
+    unsafe {
+        let base = (ptr as *mut u8).sub(HEADER);
+        let size = *(base as *const usize);
+        dealloc(
+            base,
+            Layout::from_size_align_unchecked(size + HEADER, HEADER),
+        );
+    }
+}
+
+/// Frees payloads made by `to_raw` that x264 never took, as it would have.
+pub unsafe fn free_raw(sei: &x264_sei_t) {
+    if sei.payloads.is_null() {
+        return;
+    }
+    unsafe {
+        for i in 0..sei.num_payloads as usize {
+            free((*sei.payloads.add(i)).payload as *mut c_void);
+        }
+        free(sei.payloads as *mut c_void);
+    }
+}
+
+/// Copies the payloads into memory that x264 frees once it has written them.
+pub fn to_raw(payloads: &[(i32, &[u8])]) -> x264_sei_t {
+    if payloads.is_empty() {
+        return x264_sei_t {
+            num_payloads: 0,
+            payloads: ptr::null_mut(),
+            sei_free: None,
+        };
+    }
+
+    let size = payloads.len() * mem::size_of::<x264_sei_payload_t>();
+    let raw = unsafe { allocate(size) } as *mut x264_sei_payload_t;
+
+    for (i, &(payload_type, data)) in payloads.iter().enumerate() {
+        unsafe {
+            let payload = allocate(data.len());
+            ptr::copy_nonoverlapping(data.as_ptr(), payload, data.len());
+            raw.add(i).write(x264_sei_payload_t {
+                payload_size: data.len() as i32,
+                payload_type,
+                payload,
+            });
+        }
+    }
+
+    x264_sei_t {
+        num_payloads: payloads.len() as i32,
+        payloads: raw,
+        sei_free: Some(free),
+    }
+}
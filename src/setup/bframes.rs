@@ -0,0 +1,38 @@
+use x264::*;
+
+#[derive(Clone, Copy, Hash, Debug, Eq, PartialEq)]
+#[repr(i32)]
+/// Whether bframes may be used as references for other frames.
+pub enum BPyramid {
+    /// Bframes are never used as references.
+    None = X264_B_PYRAMID_NONE as i32,
+    /// Only one bframe per minigop may be a reference, as required by Blu-ray.
+    Strict = X264_B_PYRAMID_STRICT as i32,
+    /// Bframes may be used as references freely.
+    Normal = X264_B_PYRAMID_NORMAL as i32,
+}
+
+#[derive(Clone, Copy, Hash, Debug, Eq, PartialEq)]
+#[repr(i32)]
+/// How readily adaptive bframe placement uses bframes, given as a bias from
+/// -90 to 100.
+pub enum BframeTendency {
+    /// Use bframes less often, with a bias of -50.
+    Fewer = -50,
+    /// Let x264 decide, with a bias of 0.
+    Balanced = 0,
+    /// Use bframes more often, with a bias of 50.
+    More = 50,
+}
+
+#[derive(Clone, Copy, Hash, Debug, Eq, PartialEq)]
+#[repr(i32)]
+/// How the number of consecutive bframes is chosen.
+pub enum BAdapt {
+    /// Always use the maximum.
+    None = X264_B_ADAPT_NONE as i32,
+    /// Choose quickly, which is the default.
+    Fast = X264_B_ADAPT_FAST as i32,
+    /// Choose optimally, which is slow with many bframes.
+    Trellis = X264_B_ADAPT_TRELLIS as i32,
+}
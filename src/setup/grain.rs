@@ -0,0 +1,24 @@
+#[derive(Clone, Copy, Hash, Debug, Eq, PartialEq)]
+/// How hard film grain is preserved, at the cost of bitrate.
+pub enum GrainStrength {
+    /// Keep some grain, with luma deadzones of 14 (inter) and 8 (intra) and a
+    /// psychovisual trellis strength of 0.1.
+    Light,
+    /// Keep most grain, with deadzones of 6 and a psychovisual trellis
+    /// strength of 0.25, as with `Tune::Grain`.
+    Medium,
+    /// Keep as much grain as possible, with no deadzones and a psychovisual
+    /// trellis strength of 0.4.
+    Strong,
+}
+
+impl GrainStrength {
+    // The inter and intra luma deadzones and the psy-trellis strength.
+    pub(crate) fn settings(self) -> ([i32; 2], f32) {
+        match self {
+            GrainStrength::Light => ([14, 8], 0.1),
+            GrainStrength::Medium => ([6, 6], 0.25),
+            GrainStrength::Strong => ([0, 0], 0.4),
+        }
+    }
+}
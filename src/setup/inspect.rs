@@ -0,0 +1,64 @@
+/// A snapshot of a builder's settings, as returned by `Setup::inspect`.
+///
+/// The values are as x264 holds them before opening an encoder, so automatic
+/// ones (such as a thread count of 0) haven't been resolved yet.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ParamInspection {
+    /// The framerate's numerator and denominator.
+    pub fps: (u32, u32),
+    /// The average bitrate, in kilobits per second.
+    pub bitrate: i32,
+    /// The constant rate factor, if that is the rate control method.
+    pub crf: Option<f32>,
+    /// The bit depth.
+    pub bit_depth: i32,
+    /// The level, multiplied by ten, or -1 for automatic.
+    pub level_idc: i32,
+    /// The number of reference frames.
+    pub reference_frames: i32,
+    /// The maximum number of consecutive bframes.
+    pub bframes: i32,
+    /// The subpixel motion estimation refinement, from 0 to 11.
+    pub subpel_refine: i32,
+    /// The trellis quantization mode, from 0 (off) to 2 (everywhere).
+    pub trellis: i32,
+    /// Whether CABAC is used rather than CAVLC.
+    pub cabac: bool,
+    /// The number of frames the rate control looks ahead.
+    pub rc_lookahead: i32,
+    /// The number of frames buffered for threaded lookahead, or -1 for
+    /// automatic (see `Setup::lookahead`).
+    pub sync_lookahead: i32,
+    /// The number of threads, or 0 for automatic.
+    pub threads: i32,
+    /// The number of lookahead threads.
+    pub lookahead_threads: i32,
+    /// The maximum number of frames between keyframes.
+    pub max_keyframe_interval: i32,
+    /// The minimum number of frames between keyframes.
+    pub min_keyframe_interval: i32,
+    /// The scenecut threshold.
+    pub scenecut_threshold: i32,
+    /// Whether GOPs are open.
+    pub open_gop: bool,
+    /// Whether Annex B start codes are used.
+    pub annexb: bool,
+    /// The VBV maximum rate, in kilobits per second, or 0 for none.
+    pub vbv_max_bitrate: i32,
+    /// The VBV buffer size, in kilobits, or 0 for none.
+    pub vbv_buffer_size: i32,
+    /// How far the rate control lets the bitrate stray from the target, as a
+    /// multiple of it.
+    pub rate_tolerance: f32,
+    /// Whether filler data is inserted to keep the bitrate constant.
+    pub filler: bool,
+    /// The HRD parameters signalled: `X264_NAL_HRD_NONE`, `X264_NAL_HRD_VBR`
+    /// or `X264_NAL_HRD_CBR`.
+    pub nal_hrd: i32,
+    /// The quantization matrices: `X264_CQM_FLAT`, `X264_CQM_JVT` or
+    /// `X264_CQM_CUSTOM`.
+    pub quantization_matrices: i32,
+    /// Whether the timestamps are used for rate control, rather than assuming
+    /// a constant framerate.
+    pub vfr_input: bool,
+}
@@ -0,0 +1,149 @@
+// The maximum decoded picture buffer size of each level, in macroblocks, from
+// table A-1 of the H.264 specification. Level 1b is given as 9.
+const MAX_DPB_MBS: [(i32, i32); 17] = [
+    (9, 396),
+    (10, 396),
+    (11, 900),
+    (12, 2376),
+    (13, 2376),
+    (20, 2376),
+    (21, 4752),
+    (22, 8100),
+    (30, 8100),
+    (31, 18000),
+    (32, 20480),
+    (40, 32768),
+    (41, 32768),
+    (42, 34816),
+    (50, 110400),
+    (51, 184320),
+    (52, 184320),
+];
+
+#[derive(Clone, Copy, Hash, Debug, Eq, PartialEq)]
+#[repr(i32)]
+/// An H.264 level, with its `level_idc` as the discriminant.
+///
+/// Level 1b is given as 9, as x264 does.
+pub enum Level {
+    /// Level 1b.
+    Level1b = 9,
+    /// Level 1.
+    Level1 = 10,
+    /// Level 1.1.
+    Level11 = 11,
+    /// Level 1.2.
+    Level12 = 12,
+    /// Level 1.3.
+    Level13 = 13,
+    /// Level 2.
+    Level2 = 20,
+    /// Level 2.1.
+    Level21 = 21,
+    /// Level 2.2.
+    Level22 = 22,
+    /// Level 3.
+    Level3 = 30,
+    /// Level 3.1.
+    Level31 = 31,
+    /// Level 3.2.
+    Level32 = 32,
+    /// Level 4.
+    Level4 = 40,
+    /// Level 4.1.
+    Level41 = 41,
+    /// Level 4.2.
+    Level42 = 42,
+    /// Level 5.
+    Level5 = 50,
+    /// Level 5.1.
+    Level51 = 51,
+    /// Level 5.2.
+    Level52 = 52,
+    /// Level 6.
+    Level6 = 60,
+    /// Level 6.1.
+    Level61 = 61,
+    /// Level 6.2.
+    Level62 = 62,
+}
+
+impl Level {
+    /// The level with the given `level_idc`, as used by `Setup::level`.
+    pub fn from_idc(level_idc: i32) -> Option<Self> {
+        Some(match level_idc {
+            9 => Level::Level1b,
+            10 => Level::Level1,
+            11 => Level::Level11,
+            12 => Level::Level12,
+            13 => Level::Level13,
+            20 => Level::Level2,
+            21 => Level::Level21,
+            22 => Level::Level22,
+            30 => Level::Level3,
+            31 => Level::Level31,
+            32 => Level::Level32,
+            40 => Level::Level4,
+            41 => Level::Level41,
+            42 => Level::Level42,
+            50 => Level::Level5,
+            51 => Level::Level51,
+            52 => Level::Level52,
+            60 => Level::Level6,
+            61 => Level::Level61,
+            62 => Level::Level62,
+            _ => return None,
+        })
+    }
+}
+
+/// The most reference frames that a level allows at the given size, which is
+/// the number of frames that fit in its decoded picture buffer, up to 16.
+///
+/// The level is multiplied by ten, as with `Setup::level`, with level 1b
+/// given as 9. Returns `None` for an unknown level, or if the width or height
+/// isn't positive.
+pub fn max_ref_frames(level_idc: i32, width: i32, height: i32) -> Option<i32> {
+    if width <= 0 || height <= 0 {
+        return None;
+    }
+    let max_dpb_mbs = match level_idc {
+        60..=62 => 696320,
+        _ => MAX_DPB_MBS.iter().find(|&&(idc, _)| idc == level_idc)?.1,
+    };
+    let mbs = ((width + 15) / 16) * ((height + 15) / 16);
+    Some((max_dpb_mbs / mbs).min(16))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levels_round_trip() {
+        assert_eq!(Level::from_idc(41), Some(Level::Level41));
+        assert_eq!(Level::from_idc(9), Some(Level::Level1b));
+        assert_eq!(Level::from_idc(14), None);
+        for &(idc, _) in &MAX_DPB_MBS {
+            assert_eq!(Level::from_idc(idc).map(|level| level as i32), Some(idc));
+        }
+    }
+
+    #[test]
+    fn full_hd() {
+        assert_eq!(max_ref_frames(40, 1920, 1080), Some(4));
+        assert_eq!(max_ref_frames(51, 1920, 1080), Some(16));
+    }
+
+    #[test]
+    fn unknown_level() {
+        assert_eq!(max_ref_frames(14, 1920, 1080), None);
+    }
+
+    #[test]
+    fn empty_size() {
+        assert_eq!(max_ref_frames(40, 0, 1080), None);
+        assert_eq!(max_ref_frames(40, 1920, 0), None);
+        assert_eq!(max_ref_frames(40, -1920, -1080), None);
+    }
+}
@@ -1,17 +1,36 @@
-use crate::{Encoder, Encoding, Error, Result};
+use crate::{Encoder, Encoding, Error, FrameType, Result};
+use alloc::vec::Vec;
 use core::ffi::c_char;
 use core::mem::MaybeUninit;
 use x264::*;
 
+mod bframes;
+mod grain;
+mod inspect;
+mod level;
+mod options;
 mod preset;
+mod profile;
+mod pulldown;
 mod tune;
 
+pub use self::bframes::*;
+pub use self::grain::*;
+pub use self::inspect::*;
+pub use self::level::{max_ref_frames, Level};
 pub use self::preset::*;
+pub use self::profile::*;
+pub use self::pulldown::*;
 pub use self::tune::*;
 
 /// Builds a new encoder.
 pub struct Setup {
     raw: x264_param_t,
+    forced_frame_types: Vec<(i64, FrameType)>,
+    pulldown: Pulldown,
+    first_frame_qp: Option<i32>,
+    // The first invalid argument given to a setter, reported by `build`.
+    invalid: Option<Error>,
 }
 
 impl Setup {
@@ -28,8 +47,25 @@ impl Setup {
             )
         });
 
+        unsafe { Self::from_raw(raw.assume_init()) }
+    }
+
+    /// Creates a new builder from fully prepared parameters, such as ones
+    /// configured through the raw bindings.
+    ///
+    /// # Safety
+    ///
+    /// The parameters must be valid as far as x264 is concerned, which is
+    /// easiest to ensure by starting from `x264_param_default` or
+    /// `x264_param_default_preset`. Any pointers in them (such as file names or
+    /// zones) must stay valid until the encoder is built.
+    pub unsafe fn from_raw(raw: x264_param_t) -> Self {
         Self {
-            raw: unsafe { raw.assume_init() },
+            raw,
+            forced_frame_types: Vec::new(),
+            pulldown: Pulldown::None,
+            first_frame_qp: None,
+            invalid: None,
         }
     }
 
@@ -41,6 +77,30 @@ impl Setup {
         self
     }
 
+    /// Makes the first pass faster by using the motion analysis of a lighter
+    /// preset, keeping the rate control and everything else.
+    ///
+    /// This goes further than `fastfirstpass` (which it can be combined
+    /// with), since the first pass only gathers statistics for the second.
+    /// The copied settings are the partitions, motion search method and
+    /// range, subpixel refinement, trellis, mixed references, chroma motion
+    /// estimation and fast P-skip, plus the number of reference frames.
+    pub fn first_pass_preset(mut self, preset: Preset) -> Self {
+        let light = Setup::preset(preset, Tune::None, false, false).raw;
+        let analyse = &mut self.raw.analyse;
+        analyse.intra = light.analyse.intra;
+        analyse.inter = light.analyse.inter;
+        analyse.i_me_method = light.analyse.i_me_method;
+        analyse.i_me_range = light.analyse.i_me_range;
+        analyse.i_subpel_refine = light.analyse.i_subpel_refine;
+        analyse.i_trellis = light.analyse.i_trellis;
+        analyse.b_mixed_references = light.analyse.b_mixed_references;
+        analyse.b_chroma_me = light.analyse.b_chroma_me;
+        analyse.b_fast_pskip = light.analyse.b_fast_pskip;
+        self.raw.i_frame_reference = light.i_frame_reference;
+        self
+    }
+
     /// The video's framerate, represented as a rational number.
     ///
     /// The value is in frames per second.
@@ -59,6 +119,16 @@ impl Setup {
         self
     }
 
+    /// Enable/disable using the timestamps, in the `timebase`, for rate
+    /// control. Defaults to `true`.
+    ///
+    /// Without this, x264 assumes a constant framerate and replaces the
+    /// timebase with the inverse of the framerate.
+    pub fn vfr_input(mut self, enabled: bool) -> Self {
+        self.raw.b_vfr_input = enabled as i32;
+        self
+    }
+
     /// Enable/disable Annex B start codes. Defaults to `true`.
     ///
     /// Annex B start codes are not used by containers based on the ISO BMFF
@@ -77,9 +147,66 @@ impl Setup {
         self
     }
 
+    /// Encodes at a strictly constant bitrate, for fixed-bandwidth channels.
+    ///
+    /// The value is in metric kilobits per second. This sets the average
+    /// bitrate, the VBV maximum rate and the VBV buffer (one second's worth)
+    /// to the target, tightens the rate tolerance from 1.0 to 0.1 so that
+    /// the rate control doesn't drift from it, and signals CBR HRD
+    /// parameters. Filler NAL units are inserted whenever the frames alone
+    /// would undershoot the target.
+    pub fn strict_cbr(mut self, kbps: i32) -> Self {
+        self = self.bitrate(kbps);
+        self.raw.rc.i_vbv_max_bitrate = kbps;
+        self.raw.rc.i_vbv_buffer_size = kbps;
+        self.raw.rc.f_rate_tolerance = 0.1;
+        self = self.filler(true);
+        self.raw.i_nal_hrd = X264_NAL_HRD_CBR as i32;
+        self
+    }
+
+    /// Target a constant rate factor, capped by the VBV, which is the usual
+    /// mode for streaming video on demand.
+    ///
+    /// The rates are in metric kilobits per second and the buffer size is in
+    /// kilobits. Both are required for the cap to take effect, so building
+    /// fails with `Error::InvalidVbv` if the buffer size isn't positive.
+    pub fn capped_crf(mut self, crf: f32, vbv_max_kbps: i32, vbv_buffer_kbits: i32) -> Self {
+        self.raw.rc.i_rc_method = X264_RC_CRF as i32;
+        self.raw.rc.f_rf_constant = crf;
+        self.raw.rc.i_vbv_max_bitrate = vbv_max_kbps;
+        self.raw.rc.i_vbv_buffer_size = vbv_buffer_kbits;
+        self
+    }
+
+    /// Enable/disable filler data. Defaults to `false`.
+    ///
+    /// With a VBV maximum rate set, this pads easy frames with filler NAL
+    /// units so the output never undershoots the rate.
+    pub fn filler(mut self, enabled: bool) -> Self {
+        self.raw.rc.b_filler = enabled as i32;
+        self
+    }
+
+    /// Signals whether the video uses the full range of sample values (as JPEG
+    /// does), rather than the limited range that TV and most video uses.
+    ///
+    /// This only sets the flag in the VUI, which decoders use to interpret the
+    /// samples; it doesn't convert anything. It defaults to full range for RGB
+    /// input and limited range otherwise, so full-range YUV input needs this
+    /// (see `Image::set_full_range`) or it will look washed out.
+    pub fn full_range(mut self, enabled: bool) -> Self {
+        self.raw.vui.b_fullrange = enabled as i32;
+        self
+    }
+
     /// Target a constant rate factor. Contant rate factoring results in the best objective psnr/ssim per bit (efficiency).
     ///
     /// Values go from -12 to 51 with -12 resulting in the highest bitrate/quality. The default is `23.0`. This setting conflicts with setting bitrate.
+    ///
+    /// A target that works out to a quantizer of 0 (0 at 8-bit depth, -12 at
+    /// 10-bit) encodes losslessly, which forces the High 4:4:4 Predictive
+    /// profile whatever the colorspace (see `lossless`).
     pub fn crf(mut self, target: f32, max: f32) -> Self {
         self.raw.rc.i_rc_method = X264_RC_CRF as i32;
         self.raw.rc.f_rf_constant = target;
@@ -87,22 +214,334 @@ impl Setup {
         self
     }
 
+    /// Encode losslessly, with a constant quantizer of 0.
+    ///
+    /// x264 always uses the High 4:4:4 Predictive profile for this, even for
+    /// subsampled colorspaces such as I420, so the output needs a decoder
+    /// that supports it. Only the chroma that the input has is kept.
+    pub fn lossless(mut self) -> Self {
+        self.raw.rc.i_rc_method = X264_RC_CQP as i32;
+        self.raw.rc.i_qp_constant = 0;
+        self
+    }
+
+    /// Tunes the encoder for synthetic content, such as desktop capture.
+    ///
+    /// This disables psychovisual optimizations (`psy-rd` and
+    /// `psy-trellis`), which add noise around sharp edges like text, weakens
+    /// deblocking to `-2:-2` so those edges stay crisp, and enables
+    /// macroblock info so that unchanged regions can be marked per frame.
+    pub fn screen_content(mut self) -> Self {
+        self.raw.analyse.b_psy = 0;
+        self.raw.analyse.f_psy_rd = 0.0;
+        self.raw.analyse.f_psy_trellis = 0.0;
+        self.raw.b_deblocking_filter = 1;
+        self.raw.i_deblocking_filter_alphac0 = -2;
+        self.raw.i_deblocking_filter_beta = -2;
+        self.raw.analyse.b_mb_info = 1;
+        self
+    }
+
+    /// Sets how much motion analysis is done per frame, from 0 (fastest) to
+    /// 10 (slowest), without touching anything else.
+    ///
+    /// This is a finer-grained dial than the presets for real-time encoding,
+    /// where analysis dominates the time per frame. Each level sets:
+    ///
+    /// | Level | Subpel refinement | Motion search | Partitions   | Trellis |
+    /// |-------|-------------------|---------------|--------------|---------|
+    /// | 0     | 1                 | diamond       | none         | off     |
+    /// | 1     | 1                 | diamond       | intra        | off     |
+    /// | 2     | 2                 | hexagon       | intra, 16x16 | off     |
+    /// | 3     | 4                 | hexagon       | intra, 16x16 | final   |
+    /// | 4     | 6                 | hexagon       | intra, 16x16 | final   |
+    /// | 5     | 7                 | hexagon       | intra, 16x16 | final   |
+    /// | 6     | 8                 | hexagon       | intra, 16x16 | final   |
+    /// | 7     | 8                 | multi-hexagon | intra, 16x16 | final   |
+    /// | 8     | 9                 | multi-hexagon | all          | all     |
+    /// | 9     | 10                | multi-hexagon | all          | all     |
+    /// | 10    | 11                | exhaustive    | all          | all     |
+    ///
+    /// Levels above 10 are treated as 10. Note that a subpel refinement of 10
+    /// or more requires trellis and adaptive quantization to take effect.
+    pub fn realtime_budget(mut self, level: u8) -> Self {
+        const I: u32 = X264_ANALYSE_I4x4 | X264_ANALYSE_I8x8;
+        const P: u32 = I | X264_ANALYSE_PSUB16x16 | X264_ANALYSE_BSUB16x16;
+        const ALL: u32 = P | X264_ANALYSE_PSUB8x8;
+        const LEVELS: [(i32, u32, u32, i32); 11] = [
+            (1, X264_ME_DIA, 0, 0),
+            (1, X264_ME_DIA, I, 0),
+            (2, X264_ME_HEX, P, 0),
+            (4, X264_ME_HEX, P, 1),
+            (6, X264_ME_HEX, P, 1),
+            (7, X264_ME_HEX, P, 1),
+            (8, X264_ME_HEX, P, 1),
+            (8, X264_ME_UMH, P, 1),
+            (9, X264_ME_UMH, ALL, 2),
+            (10, X264_ME_UMH, ALL, 2),
+            (11, X264_ME_TESA, ALL, 2),
+        ];
+
+        let (subme, me, partitions, trellis) = LEVELS[level.min(10) as usize];
+        self.raw.analyse.i_subpel_refine = subme;
+        self.raw.analyse.i_me_method = me as i32;
+        self.raw.analyse.intra = partitions & I;
+        self.raw.analyse.inter = partitions;
+        self.raw.analyse.i_trellis = trellis;
+        self
+    }
+
+    /// Uses adaptive quantization that is biased towards dark, flat areas,
+    /// at the given strength (1.0 by default).
+    ///
+    /// This is x264's auto-variance AQ with a bias to dark scenes (`aq-mode`
+    /// 3), which keeps banding out of dark gradients. x264 scales both the
+    /// variance-based offsets and the dark bias by the strength, so it also
+    /// controls the bias: higher values move more bits from detailed areas to
+    /// flat ones, and from bright areas to dark ones.
+    pub fn aq_mask(mut self, strength: f32) -> Self {
+        self.raw.rc.i_aq_mode = X264_AQ_AUTOVARIANCE_BIASED as i32;
+        self.raw.rc.f_aq_strength = strength;
+        self
+    }
+
+    /// Preserves film grain, at the given strength, instead of letting it be
+    /// smoothed away.
+    ///
+    /// This is a dial over the quantization settings that `Tune::Grain` also
+    /// changes: it narrows the luma deadzones (which x264 uses where trellis
+    /// quantization isn't) and strengthens psychovisual trellis, and turns off
+    /// DCT decimation and noise reduction, which would throw grain away. The
+    /// rate control is left alone, so a stronger setting costs more bits.
+    pub fn preserve_grain(mut self, strength: GrainStrength) -> Self {
+        let (deadzone, psy_trellis) = strength.settings();
+        self.raw.analyse.i_luma_deadzone = deadzone;
+        self.raw.analyse.b_psy = 1;
+        self.raw.analyse.f_psy_trellis = psy_trellis;
+        self.raw.analyse.b_dct_decimate = 0;
+        self.raw.analyse.i_noise_reduction = 0;
+        self
+    }
+
+    /// Enable/disable computing each frame's PSNR. Defaults to `false`.
+    ///
+    /// The results are given by `Picture::psnr`. This slows encoding a little.
+    pub fn psnr(mut self, enabled: bool) -> Self {
+        self.raw.analyse.b_psnr = enabled as i32;
+        self
+    }
+
+    /// Enable/disable computing each frame's SSIM. Defaults to `false`.
+    ///
+    /// The results are given by `Picture::ssim`. This slows encoding a little.
+    pub fn ssim(mut self, enabled: bool) -> Self {
+        self.raw.analyse.b_ssim = enabled as i32;
+        self
+    }
+
     /// Restricts the maximum number of bframes.
-    /// 
+    ///
     /// Setting this to 0 disable bframes.
     pub fn bframes(mut self, max: i32) -> Self {
         self.raw.i_bframe = max;
         self
     }
 
+    /// Sets the maximum number of consecutive bframes, and how many of them
+    /// are actually used each time.
+    ///
+    /// `max` must be positive with an adaptive `mode`, since no bframes
+    /// leaves nothing to adapt, or building fails with `Error::InvalidBframes`.
+    pub fn adaptive_bframes(mut self, max: i32, mode: BAdapt) -> Self {
+        if max <= 0 && mode != BAdapt::None {
+            self.reject(Error::InvalidBframes);
+        }
+        self.raw.i_bframe_adaptive = mode as i32;
+        self.bframes(max)
+    }
+
+    /// Sets whether bframes may be used as references. Defaults to
+    /// `BPyramid::Normal`.
+    ///
+    /// This must not be `BPyramid::None` for `FrameType::Bref` to be forced.
+    pub fn bframe_pyramid(mut self, mode: BPyramid) -> Self {
+        self.raw.i_bframe_pyramid = mode as i32;
+        self
+    }
+
+    /// Bounds how many frames a decoder has to hold back to put them in
+    /// display order (the `num_reorder_frames` of the stream), for pipelines
+    /// that need a latency contract.
+    ///
+    /// Bframes need one frame of reordering however many come in a row, and a
+    /// `BPyramid` needs two, so 0 disables bframes, 1 disables the pyramid,
+    /// and anything higher leaves the settings as they are. This only lowers
+    /// the current settings, so it should come after any other bframe
+    /// settings.
+    ///
+    /// This doesn't bound the encoder's own delay, which grows with the number
+    /// of consecutive bframes and the lookahead (see `bframes` and
+    /// `lookahead`).
+    ///
+    /// `frames` must not be negative, or building fails with
+    /// `Error::InvalidBframes`.
+    pub fn max_reorder(mut self, frames: i32) -> Self {
+        match frames {
+            _ if frames < 0 => self.reject(Error::InvalidBframes),
+            0 => self.raw.i_bframe = 0,
+            1 => self.raw.i_bframe_pyramid = BPyramid::None as i32,
+            _ => {}
+        }
+        self
+    }
+
+    /// Sets how readily bframes are used, up to the maximum set by `bframes`.
+    /// Defaults to `BframeTendency::Balanced`.
+    pub fn bframe_tendency(mut self, tendency: BframeTendency) -> Self {
+        self.raw.i_bframe_bias = tendency as i32;
+        self
+    }
+
+    /// Sets the bit depth of the encoded video. Defaults to 8.
+    ///
+    /// Anything higher requires a build of x264 that supports it, and input
+    /// images with the `HighDepth` modifier.
+    pub fn bit_depth(mut self, depth: i32) -> Self {
+        self.raw.i_bitdepth = depth;
+        self
+    }
+
+    /// Sets the level, multiplied by ten (e.g. `41` for level 4.1). Defaults
+    /// to choosing the lowest level that suits the other settings.
+    pub fn level(mut self, level_idc: i32) -> Self {
+        self.raw.i_level_idc = level_idc;
+        self
+    }
+
+    /// Sets the number of reference frames.
+    ///
+    /// x264 may lower this when opening the encoder, which can be checked
+    /// with `Encoder::reference_frames`.
+    pub fn reference_frames(mut self, count: i32) -> Self {
+        self.raw.i_frame_reference = count;
+        self
+    }
+
     /// Sets the number of frames to be used as a buffer for threaded lookahead
-    /// 
+    ///
     /// 0 disables threaded lookahead, which allows lower latency at the cost of reduced efficiency
     pub fn lookahead(mut self, number: i32) -> Self {
         self.raw.i_sync_lookahead = number;
         self
     }
 
+    /// Disables threaded lookahead, the same as a `lookahead` of 0.
+    ///
+    /// This lets `Encoder::drain` keep the encoder usable, and lowers latency.
+    pub fn no_sync_lookahead(self) -> Self {
+        self.lookahead(0)
+    }
+
+    /// Lets x264 pick the threaded lookahead's buffer size from the number of
+    /// threads and bframes, which is the default.
+    pub fn auto_sync_lookahead(self) -> Self {
+        self.lookahead(X264_SYNC_LOOKAHEAD_AUTO)
+    }
+
+    /// Sets the number of threads used for encoding.
+    ///
+    /// 0 lets x264 pick a count based on the number of CPUs. When sharing the
+    /// machine with another thread pool (such as Rayon's), set this together
+    /// with `lookahead_threads` to keep x264 within a fixed budget.
+    pub fn threads(mut self, count: i32) -> Self {
+        self.raw.i_threads = count;
+        self
+    }
+
+    /// Sets the number of threads used by the lookahead, separately from the
+    /// ones encoding frames.
+    ///
+    /// 0 (the default) lets x264 derive it from `threads`: a sixth of them,
+    /// or all of them with `sliced_threads`, but always at least one.
+    pub fn lookahead_threads(mut self, count: i32) -> Self {
+        self.raw.i_lookahead_threads = count;
+        self
+    }
+
+    /// Enable/disable splitting each frame into slices that are encoded in
+    /// parallel, instead of encoding several frames in parallel. Defaults to
+    /// `false`.
+    ///
+    /// This adds no frames of latency, unlike frame threading, but is less
+    /// efficient. x264 has no control over its threads' priority or affinity,
+    /// so this is the way to bound its latency while still using threads.
+    pub fn sliced_threads(mut self, enabled: bool) -> Self {
+        self.raw.b_sliced_threads = enabled as i32;
+        self
+    }
+
+    /// Sets the number of slices each frame is split into. Defaults to 0,
+    /// which lets x264 decide (usually one, unless `sliced_threads` is set).
+    ///
+    /// Slices can be decoded independently, which limits the damage of a
+    /// lost packet, at some cost to efficiency.
+    pub fn slices(mut self, count: i32) -> Self {
+        self.raw.i_slice_count = count;
+        self
+    }
+
+    /// Applies the same settings as the `zerolatency` tune, for builders that
+    /// didn't start from a preset with it.
+    ///
+    /// This disables bframes, threaded lookahead, the rate control lookahead
+    /// and macroblock tree rate control, ignores timestamps in rate control,
+    /// and uses sliced threads, so each frame is returned as soon as it is
+    /// encoded. Scenecut detection is left on, as with the tune.
+    pub fn zero_latency(mut self) -> Self {
+        self.raw.rc.i_lookahead = 0;
+        self.raw.rc.b_mb_tree = 0;
+        self.raw.b_vfr_input = 0;
+        self.bframes(0).lookahead(0).sliced_threads(true)
+    }
+
+    /// Splits frames into as many slices as it takes to keep each slice's
+    /// unit within `bytes`, including its start code or length, e.g. to fit
+    /// RTP packets in the MTU.
+    ///
+    /// This replaces any fixed number of slices. A unit can still exceed the
+    /// limit if a single macroblock doesn't fit (with a very low limit or at
+    /// very high bitrates), and the limit doesn't apply to parameter sets or
+    /// SEI, which are usually small anyway.
+    pub fn max_nal_size(mut self, bytes: i32) -> Self {
+        self.raw.i_slice_max_size = bytes;
+        self.raw.i_slice_max_mbs = 0;
+        self.slices(0)
+    }
+
+    /// Encodes on the calling thread only, with no threaded lookahead.
+    ///
+    /// This keeps the latency low and predictable, at the cost of throughput.
+    pub fn single_threaded(self) -> Self {
+        self.threads(1).lookahead_threads(1).lookahead(0)
+    }
+
+    /// Makes the output identical across runs and machines, for regression
+    /// or golden-file tests.
+    ///
+    /// This makes x264 deterministic with threads, and use its canonical
+    /// algorithms rather than ones chosen for the CPU (its assembly is already
+    /// bit-exact with its C code). The output still depends on the number of
+    /// threads, so an automatic count, which depends on the number of CPUs,
+    /// is replaced with one; set `threads` afterwards to use more.
+    pub fn bit_exact(mut self) -> Self {
+        self.raw.b_deterministic = 1;
+        self.raw.b_cpu_independent = 1;
+        if self.raw.i_threads == X264_THREADS_AUTO as i32 {
+            self.raw.i_threads = 1;
+        }
+        self
+    }
+
     /// When enabled, allows a group of pictures (GOP) to contain references to other groups of pictures
     pub fn open_gop(mut self, enabled: bool) -> Self {
         self.raw.b_open_gop = enabled as i32;
@@ -117,6 +556,18 @@ impl Setup {
         self
     }
 
+    /// The constrained baseline profile, which is what most mobile and WebRTC
+    /// decoders actually advertise.
+    ///
+    /// It is the baseline profile without flexible macroblock ordering,
+    /// arbitrary slice order and redundant slices. x264 never uses those, so
+    /// its baseline output already signals constrained baseline through the
+    /// SPS constraint flags (e.g. `avc1.42c01f`), and this is the same as
+    /// `baseline`.
+    pub fn constrained_baseline(self) -> Self {
+        self.baseline()
+    }
+
     /// A useless middleground between the baseline and high profiles.
     pub fn main(mut self) -> Self {
         unsafe {
@@ -133,6 +584,16 @@ impl Setup {
         self
     }
 
+    /// Enable/disable making the output stitchable. Defaults to `false`.
+    ///
+    /// x264 then doesn't tune the parameter sets to the content, so segments
+    /// encoded separately with the same settings (e.g. in parallel) have
+    /// identical headers and can be concatenated.
+    pub fn stitchable(mut self, enabled: bool) -> Self {
+        self.raw.b_stitchable = enabled as i32;
+        self
+    }
+
     /// Set the maximum number of frames between keyframes.
     pub fn max_keyframe_interval(mut self, interval: i32) -> Self {
         self.raw.i_keyint_max = interval;
@@ -140,11 +601,44 @@ impl Setup {
     }
 
     /// Set the minimum number of frames between keyframes.
+    ///
+    /// This must not be greater than the maximum, and x264 lowers it to half
+    /// of the maximum (plus one) if it's any greater than that.
     pub fn min_keyframe_interval(mut self, interval: i32) -> Self {
         self.raw.i_keyint_min = interval;
         self
     }
 
+    /// Lets x264 pick the minimum number of frames between keyframes, which
+    /// is the default.
+    ///
+    /// x264 uses a tenth of the maximum, but no more than one second's worth
+    /// of frames. The sentinel for this is zero, so a minimum of zero can't be
+    /// requested explicitly; the lowest real minimum is one.
+    pub fn auto_min_keyint(self) -> Self {
+        self.min_keyframe_interval(X264_KEYINT_MIN_AUTO as i32)
+    }
+
+    /// Stops a scene change from starting a new GOP less than `frames` frames
+    /// after the last keyframe, since tiny GOPs compress badly.
+    ///
+    /// This is `min_keyframe_interval` with the intent spelled out: a scene
+    /// change inside the minimum gets an I frame that doesn't start a GOP,
+    /// and it has no effect without scenecut detection. Keyframes forced
+    /// through `Image::set_frame_type` or `force_frame_types` still start a
+    /// GOP anywhere. As with the minimum, x264 lowers it to half of the
+    /// maximum keyframe interval (plus one) if it's any greater than that.
+    ///
+    /// `frames` must be at least one, since a minimum of zero means automatic
+    /// (see `auto_min_keyint`), or building fails with
+    /// `Error::InvalidKeyframeInterval`.
+    pub fn min_gop_frames(mut self, frames: i32) -> Self {
+        if frames < 1 {
+            self.reject(Error::InvalidKeyframeInterval);
+        }
+        self.min_keyframe_interval(frames)
+    }
+
     /// Set the scenecut threshold. Set this to zero to guarantee a keyframe
     /// every `max_keyframe_interval`.
     pub fn scenecut_threshold(mut self, threshold: i32) -> Self {
@@ -152,33 +646,476 @@ impl Setup {
         self
     }
 
+    /// Applies a soft telecine pattern to the input frames.
+    ///
+    /// The pattern is signalled through picture timing SEI rather than by
+    /// duplicating frames. Timestamps should advance by each frame's displayed
+    /// duration, and a picture structure set directly on the image takes
+    /// priority over the pattern.
+    pub fn pulldown(mut self, mode: Pulldown) -> Self {
+        let enabled = mode != Pulldown::None;
+        self.raw.b_pulldown = enabled as i32;
+        self.raw.b_pic_struct = enabled as i32;
+        self.pulldown = mode;
+        self
+    }
+
+    /// Forces the quantizer of the first frame, which is always a keyframe,
+    /// to give it a different quality from the rest (e.g. for thumbnails).
+    ///
+    /// x264 has no option for this, so the quantizer is forced on the first
+    /// image, as with `Image::force_qp`, unless the image forces its own. The
+    /// first frame after `Encoder::reset` counts too.
+    pub fn first_frame_qp(mut self, qp: i32) -> Self {
+        self.first_frame_qp = Some(qp);
+        self
+    }
+
+    /// Forces the frame type of the frames with the given timestamps.
+    ///
+    /// Each entry pairs a presentation timestamp with the type that the frame
+    /// fed with that timestamp should be encoded as, which is useful for
+    /// aligning keyframes with segment boundaries. A frame type set directly
+    /// on the image takes priority over this table.
+    pub fn force_frame_types(mut self, types: &[(i64, FrameType)]) -> Self {
+        self.forced_frame_types.extend_from_slice(types);
+        self.forced_frame_types.sort_by_key(|&(pts, _)| pts);
+        self
+    }
+
+    /// A GOP structure for segmented streaming (e.g. HLS or DASH).
+    ///
+    /// Sets the maximum and minimum keyframe intervals to `keyint`, so there
+    /// is an IDR frame exactly every `keyint` frames, closes the GOPs so that
+    /// each one can be decoded on its own, and uses up to 3 bframes with
+    /// `BPyramid::Normal`. Scenecut detection is disabled, so that there are
+    /// no extra I-frames within a GOP.
+    pub fn gop_streaming(self, keyint: i32) -> Self {
+        self.max_keyframe_interval(keyint)
+            .min_keyframe_interval(keyint)
+            .no_scenecut()
+            .open_gop(false)
+            .bframes(3)
+            .bframe_pyramid(BPyramid::Normal)
+    }
+
+    /// A GOP structure for archival, which favours compression over seeking.
+    ///
+    /// Sets the maximum keyframe interval to ten seconds' worth of frames at
+    /// the current framerate, leaves the minimum to x264, opens the GOPs, and
+    /// uses up to 8 bframes with `BPyramid::Normal`.
+    pub fn gop_archival(self) -> Self {
+        let keyint = match self.raw.i_fps_den {
+            0 => 250,
+            den => (10 * self.raw.i_fps_num as u64 / den as u64)
+                .min(X264_KEYINT_MAX_INFINITE as u64) as i32,
+        };
+
+        self.max_keyframe_interval(keyint)
+            .min_keyframe_interval(X264_KEYINT_MIN_AUTO as i32)
+            .open_gop(true)
+            .bframes(8)
+            .bframe_pyramid(BPyramid::Normal)
+    }
+
+    /// Replaces keyframes with periodic intra refresh, which sweeps a column of
+    /// intra blocks across the picture so that it is fully refreshed every
+    /// `frames` frames.
+    ///
+    /// x264 has no separate setting for the period: it is the maximum keyframe
+    /// interval, which this sets. This avoids the bitrate spikes of keyframes,
+    /// at the cost of slower recovery after packet loss.
+    pub fn intra_refresh_period(mut self, frames: i32) -> Self {
+        self.raw.b_intra_refresh = 1;
+        self.max_keyframe_interval(frames)
+    }
+
+    /// Disables scenecut detection, so that keyframes are only placed at the
+    /// `max_keyframe_interval`.
+    ///
+    /// This is the same as a `scenecut_threshold` of zero, and gives the
+    /// predictable GOP structure that broadcast and segmented streaming need.
+    pub fn no_scenecut(self) -> Self {
+        self.scenecut_threshold(0)
+    }
+
+    /// Places an IDR frame exactly every `frames` frames, and nowhere else.
+    ///
+    /// Sets the maximum and minimum keyframe intervals to `frames`, disables
+    /// scenecut detection and closes the GOPs, which is what aligning HLS or
+    /// DASH segments requires. Frames forced through `Image::set_frame_type`
+    /// or `force_frame_types` still take precedence.
+    pub fn idr_interval(self, frames: i32) -> Self {
+        self.max_keyframe_interval(frames)
+            .min_keyframe_interval(frames)
+            .no_scenecut()
+            .open_gop(false)
+    }
+
+    /// Reads back the current settings, e.g. to show a preset's defaults.
+    pub fn inspect(&self) -> ParamInspection {
+        let raw = &self.raw;
+        ParamInspection {
+            fps: (raw.i_fps_num, raw.i_fps_den),
+            bitrate: raw.rc.i_bitrate,
+            crf: if raw.rc.i_rc_method == X264_RC_CRF as i32 {
+                Some(raw.rc.f_rf_constant)
+            } else {
+                None
+            },
+            bit_depth: raw.i_bitdepth,
+            level_idc: raw.i_level_idc,
+            reference_frames: raw.i_frame_reference,
+            bframes: raw.i_bframe,
+            subpel_refine: raw.analyse.i_subpel_refine,
+            trellis: raw.analyse.i_trellis,
+            cabac: raw.b_cabac != 0,
+            rc_lookahead: raw.rc.i_lookahead,
+            sync_lookahead: raw.i_sync_lookahead,
+            threads: raw.i_threads,
+            lookahead_threads: raw.i_lookahead_threads,
+            max_keyframe_interval: raw.i_keyint_max,
+            min_keyframe_interval: raw.i_keyint_min,
+            scenecut_threshold: raw.i_scenecut_threshold,
+            open_gop: raw.b_open_gop != 0,
+            annexb: raw.b_annexb != 0,
+            quantization_matrices: raw.i_cqm_preset,
+            vbv_max_bitrate: raw.rc.i_vbv_max_bitrate,
+            vbv_buffer_size: raw.rc.i_vbv_buffer_size,
+            rate_tolerance: raw.rc.f_rate_tolerance,
+            filler: raw.rc.b_filler != 0,
+            nal_hrd: raw.i_nal_hrd,
+            vfr_input: raw.b_vfr_input != 0,
+        }
+    }
+
+    /// Asserts that encoders built from this and `other` (with the same
+    /// colorspace and size) will write the same parameter sets, so that their
+    /// output can be concatenated.
+    ///
+    /// This compares every setting that x264 writes into the parameter sets.
+    /// Both setups should also be `stitchable`, or x264 may still tune the
+    /// parameter sets to the content.
+    ///
+    /// # Panics
+    ///
+    /// Panics, naming the first setting that differs, if they don't match.
+    pub fn assert_matches(&self, other: &Setup) {
+        fn fields(raw: &x264_param_t) -> [(&'static str, i64); 35] {
+            let vui = &raw.vui;
+            let analyse = &raw.analyse;
+            [
+                ("bit depth", raw.i_bitdepth as i64),
+                ("level", raw.i_level_idc as i64),
+                ("reference frames", raw.i_frame_reference as i64),
+                ("bframes", raw.i_bframe as i64),
+                ("bframe pyramid", raw.i_bframe_pyramid as i64),
+                ("maximum keyframe interval", raw.i_keyint_max as i64),
+                ("CABAC", raw.b_cabac as i64),
+                ("interlacing", raw.b_interlaced as i64),
+                ("fake interlacing", raw.b_fake_interlaced as i64),
+                ("constrained intra", raw.b_constrained_intra as i64),
+                ("quantization matrices", raw.i_cqm_preset as i64),
+                ("8x8 transforms", analyse.b_transform_8x8 as i64),
+                ("weighted prediction", analyse.i_weighted_pred as i64),
+                ("weighted biprediction", analyse.b_weighted_bipred as i64),
+                ("direct prediction", analyse.i_direct_mv_pred as i64),
+                ("chroma QP offset", analyse.i_chroma_qp_offset as i64),
+                ("framerate numerator", raw.i_fps_num as i64),
+                ("framerate denominator", raw.i_fps_den as i64),
+                ("timebase numerator", raw.i_timebase_num as i64),
+                ("timebase denominator", raw.i_timebase_den as i64),
+                ("VFR input", raw.b_vfr_input as i64),
+                ("picture structure", raw.b_pic_struct as i64),
+                ("HRD", raw.i_nal_hrd as i64),
+                ("VBV maximum rate", raw.rc.i_vbv_max_bitrate as i64),
+                ("VBV buffer size", raw.rc.i_vbv_buffer_size as i64),
+                ("stitchable", raw.b_stitchable as i64),
+                ("sample aspect ratio width", vui.i_sar_width as i64),
+                ("sample aspect ratio height", vui.i_sar_height as i64),
+                ("overscan", vui.i_overscan as i64),
+                ("video format", vui.i_vidformat as i64),
+                ("chroma sample location", vui.i_chroma_loc as i64),
+                ("full range", vui.b_fullrange as i64),
+                ("color primaries", vui.i_colorprim as i64),
+                ("color matrix", vui.i_colmatrix as i64),
+                ("transfer characteristics", vui.i_transfer as i64),
+            ]
+        }
+
+        for (&(name, a), &(_, b)) in fields(&self.raw).iter().zip(fields(&other.raw).iter()) {
+            assert!(a == b, "the setups differ in their {}", name);
+        }
+    }
+
     /// Build the encoder.
+    ///
+    /// # Errors
+    ///
+    /// Fails with:
+    ///
+    /// - `Error::InvalidDimensions` if the width or height isn't positive, or
+    ///   isn't a multiple of the colorspace's chroma subsampling (e.g. odd
+    ///   dimensions with 4:2:0).
+    /// - `Error::InvalidFramerate` if the framerate is zero.
+    /// - `Error::InvalidKeyframeInterval` if the minimum keyframe interval is
+    ///   greater than the maximum, or `min_gop_frames` was given less than one.
+    /// - `Error::InvalidVbv` if a VBV maximum rate was set without a buffer
+    ///   size, which x264 would otherwise ignore.
+    /// - `Error::InvalidBframes` if `adaptive_bframes` was given no bframes
+    ///   to adapt, or `max_reorder` a negative bound.
+    /// - `Error::TooManyReferenceFrames` if a level was set that can't hold
+    ///   that many reference frames at this size.
+    /// - `Error::Open` if x264 rejects the parameters.
     pub fn build<C>(mut self, csp: C, width: i32, height: i32) -> Result<Encoder>
     where
         C: Into<Encoding>,
     {
-        self.raw.i_csp = csp.into().into_raw();
+        let csp = csp.into();
+        self.validate(csp, width, height)?;
+
+        self.raw.i_csp = csp.into_raw();
         self.raw.i_width = width;
         self.raw.i_height = height;
 
         let raw = unsafe { x264_encoder_open(&mut self.raw) };
 
         if raw.is_null() {
-            Err(Error)
+            Err(Error::Open)
         } else {
-            Ok(unsafe { Encoder::from_raw(raw) })
+            let mut encoder = unsafe { Encoder::from_raw(raw) };
+            encoder.forced_frame_types = self.forced_frame_types;
+            encoder.pulldown = self.pulldown;
+            encoder.first_frame_qp = self.first_frame_qp;
+            Ok(encoder)
+        }
+    }
+
+    // Keeps the first error, since later ones may follow from it.
+    fn reject(&mut self, error: Error) {
+        self.invalid = self.invalid.or(Some(error));
+    }
+
+    fn validate(&self, csp: Encoding, width: i32, height: i32) -> Result<()> {
+        if let Some(error) = self.invalid {
+            return Err(error);
+        }
+
+        let (_, wm, hm, _, _) = csp.colorspace().layout();
+        if width <= 0 || height <= 0 || width % wm != 0 || height % hm != 0 {
+            return Err(Error::InvalidDimensions);
+        }
+
+        if self.raw.i_fps_num == 0 || self.raw.i_fps_den == 0 {
+            return Err(Error::InvalidFramerate);
+        }
+
+        if self.raw.i_keyint_min > self.raw.i_keyint_max {
+            return Err(Error::InvalidKeyframeInterval);
+        }
+
+        if self.raw.rc.i_vbv_max_bitrate > 0 && self.raw.rc.i_vbv_buffer_size <= 0 {
+            return Err(Error::InvalidVbv);
+        }
+
+        if let Some(max) = max_ref_frames(self.raw.i_level_idc, width, height) {
+            if self.raw.i_frame_reference > max {
+                return Err(Error::TooManyReferenceFrames);
+            }
         }
+
+        Ok(())
     }
 }
 
 impl Default for Setup {
     fn default() -> Self {
-        let raw = unsafe {
+        unsafe {
             let mut raw = MaybeUninit::uninit();
             x264_param_default(raw.as_mut_ptr());
-            raw.assume_init()
+            Self::from_raw(raw.assume_init())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Colorspace, Profile};
+
+    #[test]
+    fn subsampled_lossless_uses_high_444() {
+        let encoder = Setup::default()
+            .lossless()
+            .build(Colorspace::I420, 64, 64)
+            .unwrap();
+        assert_eq!(encoder.profile(), Profile::High444Predictive);
+    }
+
+    #[test]
+    fn gop_streaming_has_fixed_keyframes() {
+        let inspection = Setup::default().gop_streaming(48).inspect();
+        assert_eq!(inspection.max_keyframe_interval, 48);
+        assert_eq!(inspection.min_keyframe_interval, 48);
+        assert_eq!(inspection.scenecut_threshold, 0);
+        assert!(!inspection.open_gop);
+    }
+
+    #[test]
+    fn matching_setups_share_parameter_sets() {
+        let a = Setup::default().stitchable(true).crf(20.0, 0.0);
+        let b = Setup::default().stitchable(true).crf(28.0, 0.0);
+        a.assert_matches(&b);
+
+        let mut a = a.build(Colorspace::I420, 64, 64).unwrap();
+        let mut b = b.build(Colorspace::I420, 64, 64).unwrap();
+        assert_eq!(a.parameter_sets().unwrap(), b.parameter_sets().unwrap());
+    }
+
+    #[test]
+    #[should_panic(expected = "VBV maximum rate")]
+    fn vbv_settings_must_match() {
+        let a = Setup::default().stitchable(true);
+        let b = Setup::default()
+            .stitchable(true)
+            .capped_crf(23.0, 1000, 2000);
+        a.assert_matches(&b);
+    }
+
+    #[test]
+    fn constrained_baseline_sets_the_constraint_flags() {
+        let mut encoder = Setup::default()
+            .constrained_baseline()
+            .build(Colorspace::I420, 64, 64)
+            .unwrap();
+        let sps = encoder.sps().unwrap();
+        assert_eq!(sps[1], Profile::Baseline as u8);
+        // constraint_set0_flag and constraint_set1_flag.
+        assert_eq!(sps[2] & 0xc0, 0xc0);
+    }
+
+    #[test]
+    fn strict_cbr_fills_to_the_target() {
+        let inspection = Setup::default().strict_cbr(4000).inspect();
+        assert_eq!(inspection.bitrate, 4000);
+        assert_eq!(inspection.crf, None);
+        assert_eq!(inspection.vbv_max_bitrate, 4000);
+        assert_eq!(inspection.vbv_buffer_size, 4000);
+        assert!(inspection.rate_tolerance < 1.0);
+        assert!(inspection.filler);
+        assert_eq!(inspection.nal_hrd, X264_NAL_HRD_CBR as i32);
+    }
+
+    // The error building an I420 encoder of the given size fails with.
+    fn build_error(setup: Setup, width: i32, height: i32) -> Option<Error> {
+        setup.build(Colorspace::I420, width, height).err()
+    }
+
+    #[test]
+    fn odd_subsampled_sizes_are_rejected() {
+        assert_eq!(
+            build_error(Setup::default(), 63, 64),
+            Some(Error::InvalidDimensions)
+        );
+        assert_eq!(
+            build_error(Setup::default(), 64, 0),
+            Some(Error::InvalidDimensions)
+        );
+    }
+
+    #[test]
+    fn zero_framerates_are_rejected() {
+        assert_eq!(
+            build_error(Setup::default().fps(0, 1), 64, 64),
+            Some(Error::InvalidFramerate)
+        );
+        assert_eq!(
+            build_error(Setup::default().fps(30, 0), 64, 64),
+            Some(Error::InvalidFramerate)
+        );
+    }
+
+    #[test]
+    fn inverted_keyframe_intervals_are_rejected() {
+        let setup = Setup::default()
+            .max_keyframe_interval(10)
+            .min_keyframe_interval(20);
+        assert_eq!(
+            build_error(setup, 64, 64),
+            Some(Error::InvalidKeyframeInterval)
+        );
+    }
+
+    #[test]
+    fn references_beyond_the_level_are_rejected() {
+        // Level 4.0 fits 4 frames of 1080p.
+        let setup = || Setup::default().level(40);
+        assert_eq!(
+            build_error(setup().reference_frames(5), 1920, 1080),
+            Some(Error::TooManyReferenceFrames)
+        );
+        assert_eq!(build_error(setup().reference_frames(4), 1920, 1080), None);
+    }
+
+    #[test]
+    fn gop_archival_spans_ten_seconds() {
+        let keyint = |num, den| {
+            Setup::default()
+                .fps(num, den)
+                .gop_archival()
+                .inspect()
+                .max_keyframe_interval
         };
+        assert_eq!(keyint(30000, 1001), 299);
+        assert_eq!(keyint(25, 1), 250);
+        assert_eq!(keyint(u32::MAX, 1), X264_KEYINT_MAX_INFINITE as i32);
+    }
+
+    #[test]
+    fn adaptive_bframes_need_bframes() {
+        assert_eq!(
+            build_error(Setup::default().adaptive_bframes(0, BAdapt::Fast), 64, 64),
+            Some(Error::InvalidBframes)
+        );
+        assert_eq!(
+            build_error(Setup::default().adaptive_bframes(0, BAdapt::None), 64, 64),
+            None
+        );
+    }
+
+    #[test]
+    fn min_gop_frames_must_be_positive() {
+        assert_eq!(
+            build_error(Setup::default().min_gop_frames(0), 64, 64),
+            Some(Error::InvalidKeyframeInterval)
+        );
+    }
+
+    #[test]
+    fn min_gop_frames_is_clamped_to_half_the_maximum() {
+        let setup = || Setup::default().max_keyframe_interval(10);
+        let encoder = setup()
+            .min_gop_frames(8)
+            .build(Colorspace::I420, 64, 64)
+            .unwrap();
+        assert_eq!(encoder.min_keyframe_interval(), 6);
+
+        let encoder = setup()
+            .min_gop_frames(4)
+            .build(Colorspace::I420, 64, 64)
+            .unwrap();
+        assert_eq!(encoder.min_keyframe_interval(), 4);
+        assert_eq!(
+            build_error(setup().min_gop_frames(11), 64, 64),
+            Some(Error::InvalidKeyframeInterval)
+        );
+    }
 
-        Self { raw }
+    #[test]
+    fn negative_reorder_bounds_are_rejected() {
+        assert_eq!(
+            build_error(Setup::default().max_reorder(-1), 64, 64),
+            Some(Error::InvalidBframes)
+        );
     }
 }
@@ -0,0 +1,293 @@
+use super::Setup;
+use crate::{Error, Result};
+use alloc::ffi::CString;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::{fmt, ptr};
+use x264::*;
+
+// The names x264 parses for these settings, indexed by value.
+const ME_NAMES: [&str; 5] = ["dia", "hex", "umh", "esa", "tesa"];
+const DIRECT_NAMES: [&str; 4] = ["none", "spatial", "temporal", "auto"];
+const NAL_HRD_NAMES: [&str; 3] = ["none", "vbr", "cbr"];
+
+impl Setup {
+    /// Writes the settings as a string of x264 options, in the
+    /// `name=value:name=value` form of x264's `--x264opts`.
+    ///
+    /// This covers the rate control, frame type, analysis and bitstream
+    /// settings, including the quantization matrices and whether timestamps
+    /// are used, which is enough to reproduce an encode. The framerate,
+    /// timebase, pulldown and forced frame types belong to the input rather
+    /// than the configuration, so they are left out, as are the colour
+    /// description and the partitions searched in intra frames, which x264 has
+    /// no options for.
+    pub fn to_options_string(&self) -> String {
+        let raw = &self.raw;
+        let analyse = &raw.analyse;
+        let rc = &raw.rc;
+        let mut options = Vec::new();
+        let mut push = |name: &str, value: &dyn fmt::Display| {
+            options.push(alloc::format!("{}={}", name, value));
+        };
+
+        push("threads", &raw.i_threads);
+        push("lookahead-threads", &raw.i_lookahead_threads);
+        push("sliced-threads", &raw.b_sliced_threads);
+        push("sync-lookahead", &raw.i_sync_lookahead);
+        push("deterministic", &raw.b_deterministic);
+        push("cpu-independent", &raw.b_cpu_independent);
+        push("bitdepth", &raw.i_bitdepth);
+        if raw.i_level_idc > 0 {
+            push("level", &raw.i_level_idc);
+        }
+        if let Some(name) = NAL_HRD_NAMES.get(raw.i_nal_hrd as usize) {
+            push("nal-hrd", name);
+        }
+
+        // Rate control.
+        match rc.i_rc_method as u32 {
+            X264_RC_CQP => push("qp", &rc.i_qp_constant),
+            X264_RC_CRF => {
+                push("crf", &rc.f_rf_constant);
+                push("crf-max", &rc.f_rf_constant_max);
+            }
+            _ => push("bitrate", &rc.i_bitrate),
+        }
+        push("qpmin", &rc.i_qp_min);
+        push("qpmax", &rc.i_qp_max);
+        push("qpstep", &rc.i_qp_step);
+        push("ratetol", &rc.f_rate_tolerance);
+        push("vbv-maxrate", &rc.i_vbv_max_bitrate);
+        push("vbv-bufsize", &rc.i_vbv_buffer_size);
+        push("vbv-init", &rc.f_vbv_buffer_init);
+        push("ipratio", &rc.f_ip_factor);
+        push("pbratio", &rc.f_pb_factor);
+        push("filler", &rc.b_filler);
+        push("aq-mode", &rc.i_aq_mode);
+        push("aq-strength", &rc.f_aq_strength);
+        push("mbtree", &rc.b_mb_tree);
+        push("rc-lookahead", &rc.i_lookahead);
+        push("qcomp", &rc.f_qcompress);
+        push("qblur", &rc.f_qblur);
+        push("cplxblur", &rc.f_complexity_blur);
+
+        // Frame types.
+        push("ref", &raw.i_frame_reference);
+        push("keyint", &raw.i_keyint_max);
+        push("min-keyint", &raw.i_keyint_min);
+        push("scenecut", &raw.i_scenecut_threshold);
+        push("intra-refresh", &raw.b_intra_refresh);
+        push("bframes", &raw.i_bframe);
+        push("b-adapt", &raw.i_bframe_adaptive);
+        push("b-bias", &raw.i_bframe_bias);
+        push("b-pyramid", &raw.i_bframe_pyramid);
+        push("open-gop", &raw.b_open_gop);
+        push("bluray-compat", &raw.b_bluray_compat);
+
+        // Analysis.
+        let mut partitions = Vec::new();
+        for &(flag, name) in &[
+            (X264_ANALYSE_I4x4, "i4x4"),
+            (X264_ANALYSE_I8x8, "i8x8"),
+            (X264_ANALYSE_PSUB16x16, "p8x8"),
+            (X264_ANALYSE_PSUB8x8, "p4x4"),
+            (X264_ANALYSE_BSUB16x16, "b8x8"),
+        ] {
+            if analyse.inter & flag != 0 {
+                partitions.push(name);
+            }
+        }
+        if partitions.is_empty() {
+            partitions.push("none");
+        }
+        push("partitions", &partitions.join(","));
+        if let Some(name) = ME_NAMES.get(analyse.i_me_method as usize) {
+            push("me", name);
+        }
+        if let Some(name) = DIRECT_NAMES.get(analyse.i_direct_mv_pred as usize) {
+            push("direct", name);
+        }
+        push("merange", &analyse.i_me_range);
+        push("mvrange", &analyse.i_mv_range);
+        push("mvrange-thread", &analyse.i_mv_range_thread);
+        push("subme", &analyse.i_subpel_refine);
+        push("chroma-me", &analyse.b_chroma_me);
+        push("mixed-refs", &analyse.b_mixed_references);
+        push("8x8dct", &analyse.b_transform_8x8);
+        push("weightb", &analyse.b_weighted_bipred);
+        push("weightp", &analyse.i_weighted_pred);
+        push("chroma-qp-offset", &analyse.i_chroma_qp_offset);
+        push("trellis", &analyse.i_trellis);
+        push("fast-pskip", &analyse.b_fast_pskip);
+        push("dct-decimate", &analyse.b_dct_decimate);
+        push("nr", &analyse.i_noise_reduction);
+        push("deadzone-inter", &analyse.i_luma_deadzone[0]);
+        push("deadzone-intra", &analyse.i_luma_deadzone[1]);
+        push("psy", &analyse.b_psy);
+        push(
+            "psy-rd",
+            &alloc::format!("{},{}", analyse.f_psy_rd, analyse.f_psy_trellis),
+        );
+        push("psnr", &analyse.b_psnr);
+        push("ssim", &analyse.b_ssim);
+
+        // Bitstream.
+        push("cabac", &raw.b_cabac);
+        push("cabac-idc", &raw.i_cabac_init_idc);
+        if raw.b_deblocking_filter != 0 {
+            push(
+                "deblock",
+                &alloc::format!(
+                    "{},{}",
+                    raw.i_deblocking_filter_alphac0,
+                    raw.i_deblocking_filter_beta
+                ),
+            );
+        } else {
+            push("deblock", &"false");
+        }
+        if raw.b_interlaced != 0 {
+            push(if raw.b_tff != 0 { "tff" } else { "bff" }, &"1");
+        }
+        push("fake-interlaced", &raw.b_fake_interlaced);
+        match raw.i_cqm_preset as u32 {
+            X264_CQM_FLAT => push("cqm", &"flat"),
+            X264_CQM_JVT => push("cqm", &"jvt"),
+            _ => {
+                for &(name, matrix) in &[
+                    ("cqm4iy", &raw.cqm_4iy[..]),
+                    ("cqm4py", &raw.cqm_4py[..]),
+                    ("cqm4ic", &raw.cqm_4ic[..]),
+                    ("cqm4pc", &raw.cqm_4pc[..]),
+                    ("cqm8iy", &raw.cqm_8iy[..]),
+                    ("cqm8py", &raw.cqm_8py[..]),
+                    ("cqm8ic", &raw.cqm_8ic[..]),
+                    ("cqm8pc", &raw.cqm_8pc[..]),
+                ] {
+                    let values: Vec<String> =
+                        matrix.iter().map(|v| alloc::format!("{}", v)).collect();
+                    push(name, &values.join(","));
+                }
+            }
+        }
+        push("constrained-intra", &raw.b_constrained_intra);
+        if raw.vui.b_fullrange >= 0 {
+            let range = if raw.vui.b_fullrange != 0 { "pc" } else { "tv" };
+            push("range", &range);
+        }
+        if raw.vui.i_sar_width > 0 && raw.vui.i_sar_height > 0 {
+            push(
+                "sar",
+                &alloc::format!("{}/{}", raw.vui.i_sar_width, raw.vui.i_sar_height),
+            );
+        }
+        push("slices", &raw.i_slice_count);
+        push("slices-max", &raw.i_slice_count_max);
+        push("slice-max-size", &raw.i_slice_max_size);
+        push("slice-max-mbs", &raw.i_slice_max_mbs);
+        push("slice-min-mbs", &raw.i_slice_min_mbs);
+        // x264 only has the inverse option.
+        push("force-cfr", &((raw.b_vfr_input == 0) as i32));
+        push("aud", &raw.b_aud);
+        push("repeat-headers", &raw.b_repeat_headers);
+        push("annexb", &raw.b_annexb);
+        push("stitchable", &raw.b_stitchable);
+
+        options.join(":")
+    }
+
+    /// Makes a new builder with default options, changed by a string of
+    /// x264 options in the form written by `to_options_string`.
+    ///
+    /// Any option x264 understands can be given, with `name` alone meaning
+    /// `name=1`.
+    ///
+    /// # Errors
+    ///
+    /// Fails with `Error::InvalidOption` if x264 doesn't know an option's
+    /// name or can't parse its value.
+    pub fn from_options_string(options: &str) -> Result<Setup> {
+        let mut setup = Setup::default();
+
+        for option in options.split(':').filter(|option| !option.is_empty()) {
+            let (name, value) = match option.split_once('=') {
+                Some((name, value)) => (name, Some(value)),
+                None => (option, None),
+            };
+            setup.parse(name, value)?;
+        }
+
+        Ok(setup)
+    }
+
+    fn parse(&mut self, name: &str, value: Option<&str>) -> Result<()> {
+        let name = CString::new(name).map_err(|_| Error::InvalidOption)?;
+        let value = match value {
+            Some(value) => Some(CString::new(value).map_err(|_| Error::InvalidOption)?),
+            None => None,
+        };
+        let value_ptr = value.as_ref().map_or(ptr::null(), |value| value.as_ptr());
+
+        if unsafe { x264_param_parse(&mut self.raw, name.as_ptr(), value_ptr) } != 0 {
+            return Err(Error::InvalidOption);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn options_round_trip() {
+        let setup = Setup::default()
+            .crf(20.0, 0.0)
+            .bframes(2)
+            .reference_frames(2)
+            .level(41)
+            .no_scenecut();
+        let options = setup.to_options_string();
+        assert!(options.contains(":crf=20:"));
+        assert!(options.contains(":bframes=2:"));
+
+        let parsed = Setup::from_options_string(&options).unwrap();
+        assert_eq!(parsed.to_options_string(), options);
+        assert_eq!(parsed.inspect(), setup.inspect());
+    }
+
+    #[test]
+    fn matrices_and_timestamps_round_trip() {
+        let setup = Setup::from_options_string("cqm=jvt:force-cfr=1").unwrap();
+        let inspection = setup.inspect();
+        assert_eq!(inspection.quantization_matrices, X264_CQM_JVT as i32);
+        assert!(!inspection.vfr_input);
+
+        let parsed = Setup::from_options_string(&setup.to_options_string()).unwrap();
+        assert_eq!(parsed.inspect(), inspection);
+    }
+
+    #[test]
+    fn custom_matrices_round_trip() {
+        let flat16 = alloc::vec!["16"; 16].join(",");
+        let setup = Setup::from_options_string(&alloc::format!("cqm4iy={}", flat16)).unwrap();
+        assert_eq!(
+            setup.inspect().quantization_matrices,
+            X264_CQM_CUSTOM as i32
+        );
+
+        let options = setup.to_options_string();
+        let parsed = Setup::from_options_string(&options).unwrap();
+        assert_eq!(parsed.to_options_string(), options);
+    }
+
+    #[test]
+    fn unknown_options_are_rejected() {
+        assert!(matches!(
+            Setup::from_options_string("crf=20:no-such-option=1"),
+            Err(Error::InvalidOption)
+        ));
+    }
+}
@@ -0,0 +1,50 @@
+use crate::ChromaFormat;
+use x264::*;
+
+#[derive(Clone, Copy, Hash, Debug, Eq, PartialEq)]
+#[repr(i32)]
+/// An H.264 profile, with its `profile_idc` as the discriminant.
+pub enum Profile {
+    /// The baseline profile, without bframes or CABAC.
+    Baseline = 66,
+    /// The main profile.
+    Main = 77,
+    /// The high profile, which adds 8x8 transforms and custom quantization
+    /// matrices.
+    High = 100,
+    /// The high profile with up to 10 bits per sample.
+    High10 = 110,
+    /// The high profile with 4:2:2 chroma.
+    High422 = 122,
+    /// The high profile with 4:4:4 chroma and lossless coding.
+    High444Predictive = 244,
+}
+
+impl Profile {
+    // Mirrors how x264 picks the profile written to the SPS.
+    pub(crate) fn resolve(params: &x264_param_t, chroma: ChromaFormat) -> Self {
+        let rc = &params.rc;
+        let analyse = &params.analyse;
+        // x264 resolves a CRF to its quantizer, so this covers both.
+        let lossless = rc.i_rc_method != X264_RC_ABR as i32 && rc.i_qp_constant == 0;
+
+        if lossless || chroma == ChromaFormat::Yuv444 {
+            Profile::High444Predictive
+        } else if chroma == ChromaFormat::Yuv422 {
+            Profile::High422
+        } else if params.i_bitdepth > 8 {
+            Profile::High10
+        } else if analyse.b_transform_8x8 != 0 || params.i_cqm_preset != X264_CQM_FLAT as i32 {
+            Profile::High
+        } else if params.b_cabac != 0
+            || params.i_bframe > 0
+            || params.b_interlaced != 0
+            || params.b_fake_interlaced != 0
+            || analyse.i_weighted_pred > 0
+        {
+            Profile::Main
+        } else {
+            Profile::Baseline
+        }
+    }
+}
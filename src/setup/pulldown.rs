@@ -0,0 +1,109 @@
+use crate::PicStruct;
+
+#[derive(Clone, Copy, Hash, Debug, Eq, PartialEq)]
+/// A soft telecine pattern, which repeats fields instead of whole frames.
+pub enum Pulldown {
+    /// No pulldown.
+    None,
+    /// Every frame is displayed as two fields.
+    TwoTwo,
+    /// The classic 3:2 pattern, for showing 24 fps film at 30 fps.
+    ThreeTwo,
+    /// Frames are alternately doubled and tripled.
+    SixFour,
+    /// Every frame is displayed twice.
+    Double,
+    /// Every frame is displayed three times.
+    Triple,
+    /// The 2:2:2:2:2:2:2:2:2:2:2:3 pattern, for showing 24 fps film at 25 fps.
+    Euro,
+}
+
+impl Pulldown {
+    /// The picture structure the pattern assigns to the `n`th frame.
+    pub fn pic_struct(self, n: u64) -> PicStruct {
+        use self::PicStruct::*;
+
+        const EURO: [PicStruct; 24] = [
+            TopBottomTop,
+            BottomTop,
+            BottomTop,
+            BottomTop,
+            BottomTop,
+            BottomTop,
+            BottomTop,
+            BottomTop,
+            BottomTop,
+            BottomTop,
+            BottomTop,
+            BottomTop,
+            BottomTopBottom,
+            TopBottom,
+            TopBottom,
+            TopBottom,
+            TopBottom,
+            TopBottom,
+            TopBottom,
+            TopBottom,
+            TopBottom,
+            TopBottom,
+            TopBottom,
+            TopBottom,
+        ];
+
+        let pattern: &[PicStruct] = match self {
+            Pulldown::None => &[Auto],
+            Pulldown::TwoTwo => &[TopBottom],
+            Pulldown::ThreeTwo => &[TopBottomTop, BottomTop, BottomTopBottom, TopBottom],
+            Pulldown::SixFour => &[Double, Triple],
+            Pulldown::Double => &[Double],
+            Pulldown::Triple => &[Triple],
+            Pulldown::Euro => &EURO,
+        };
+
+        pattern[(n % pattern.len() as u64) as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PicStruct::*;
+
+    #[test]
+    fn three_two_repeats_every_four_frames() {
+        let pattern: alloc::vec::Vec<PicStruct> =
+            (0..8).map(|n| Pulldown::ThreeTwo.pic_struct(n)).collect();
+        assert_eq!(
+            pattern,
+            [
+                TopBottomTop,
+                BottomTop,
+                BottomTopBottom,
+                TopBottom,
+                TopBottomTop,
+                BottomTop,
+                BottomTopBottom,
+                TopBottom
+            ]
+        );
+    }
+
+    #[test]
+    fn euro_adds_two_fields_every_24_frames() {
+        // Each frame has two fields, and each of the two tripled ones adds one.
+        let fields: usize = (0..24)
+            .map(|n| match Pulldown::Euro.pic_struct(n) {
+                TopBottomTop | BottomTopBottom => 3,
+                _ => 2,
+            })
+            .sum();
+        assert_eq!(fields, 50);
+    }
+
+    #[test]
+    fn none_leaves_pictures_alone() {
+        assert_eq!(Pulldown::None.pic_struct(0), Auto);
+        assert_eq!(Pulldown::None.pic_struct(5), Auto);
+    }
+}
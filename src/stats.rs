@@ -0,0 +1,235 @@
+use crate::{FrameType, Picture};
+
+/// The most consecutive bframes x264 will ever produce.
+const MAX_BFRAMES: usize = 16;
+
+/// Tracks how many consecutive bframes the encoder chose between references.
+///
+/// Feed it the type of every returned picture, in the order they were
+/// returned, which is decode order: each reference comes out before the
+/// bframes displayed ahead of it. The runs are counted between consecutive
+/// I/P frames, so referenced bframes count towards a run. Call `finish` after
+/// the last picture, so that the final run is counted too.
+#[derive(Clone, Debug, Default)]
+pub struct FrameTypeHistory {
+    anchors: u64,
+    run: usize,
+    runs: [u64; MAX_BFRAMES + 1],
+}
+
+impl FrameTypeHistory {
+    /// Makes a new, empty history.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the type of the next returned picture.
+    pub fn push(&mut self, frame_type: FrameType) {
+        match frame_type {
+            FrameType::B | FrameType::Bref => self.run += 1,
+            FrameType::Auto => {}
+            _ => {
+                // The bframes returned since the last reference are displayed
+                // before it, so they end the run leading up to it. Those
+                // after the first reference (e.g. the leading bframes of an
+                // open GOP) have no earlier reference to count from.
+                if self.anchors >= 2 {
+                    self.record();
+                }
+                self.anchors += 1;
+                self.run = 0;
+            }
+        }
+    }
+
+    /// Records the run leading up to the last reference, once every picture
+    /// has been pushed (e.g. after flushing the encoder).
+    ///
+    /// The history can then be fed another sequence.
+    pub fn finish(&mut self) {
+        if self.anchors >= 2 {
+            self.record();
+        }
+        self.anchors = 0;
+        self.run = 0;
+    }
+
+    fn record(&mut self) {
+        self.runs[self.run.min(MAX_BFRAMES)] += 1;
+    }
+
+    /// How many times each run length occurred, indexed by run length.
+    pub fn runs(&self) -> &[u64] {
+        &self.runs
+    }
+
+    /// The longest run that occurred, if any run has completed.
+    pub fn longest_run(&self) -> Option<usize> {
+        self.runs.iter().rposition(|&n| n != 0)
+    }
+
+    /// The average run length, if any run has completed.
+    pub fn average_run(&self) -> Option<f64> {
+        let (count, total) = self
+            .runs
+            .iter()
+            .enumerate()
+            .fold((0, 0), |(count, total), (len, &n)| {
+                (count + n, total + len as u64 * n)
+            });
+
+        if count == 0 {
+            None
+        } else {
+            Some(total as f64 / count as f64)
+        }
+    }
+}
+
+/// Totals for one class of frames.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TypeStats {
+    frames: u64,
+    bytes: u64,
+    qp_total: i64,
+}
+
+impl TypeStats {
+    /// The number of frames.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// The total size of the frames, in bytes.
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// The average quantizer of the frames, if there were any.
+    pub fn average_qp(&self) -> Option<f64> {
+        if self.frames == 0 {
+            None
+        } else {
+            Some(self.qp_total as f64 / self.frames as f64)
+        }
+    }
+
+    fn record(&mut self, bytes: usize, qp: i32) {
+        self.frames += 1;
+        self.bytes += bytes as u64;
+        self.qp_total += qp as i64;
+    }
+}
+
+/// Running totals of the frames an encoder has returned, by frame type.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct EncodeStats {
+    i: TypeStats,
+    p: TypeStats,
+    b: TypeStats,
+}
+
+impl EncodeStats {
+    /// Intra frames, including IDR frames.
+    pub fn i(&self) -> &TypeStats {
+        &self.i
+    }
+
+    /// Predicted frames.
+    pub fn p(&self) -> &TypeStats {
+        &self.p
+    }
+
+    /// Bi-directional frames, including referenced ones.
+    pub fn b(&self) -> &TypeStats {
+        &self.b
+    }
+
+    /// The number of frames of all types.
+    pub fn frames(&self) -> u64 {
+        self.i.frames + self.p.frames + self.b.frames
+    }
+
+    /// The total size of the frames of all types, in bytes.
+    pub fn bytes(&self) -> u64 {
+        self.i.bytes + self.p.bytes + self.b.bytes
+    }
+
+    pub(crate) fn record(&mut self, picture: &Picture, bytes: usize) {
+        let stats = match picture.frame_type() {
+            FrameType::P => &mut self.p,
+            FrameType::B | FrameType::Bref => &mut self.b,
+            _ => &mut self.i,
+        };
+        stats.record(bytes, picture.qp());
+    }
+}
+
+/// Averages of the per-frame quality metrics over all the frames returned,
+/// like the means x264 logs when it closes.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct GlobalMetrics {
+    psnr_frames: u64,
+    psnr_total: [f64; 3],
+    ssim_frames: u64,
+    ssim_total: f64,
+}
+
+impl GlobalMetrics {
+    /// The mean PSNR of each plane (Y, U then V), in decibels, if it was
+    /// computed for any frames.
+    pub fn psnr(&self) -> Option<[f64; 3]> {
+        if self.psnr_frames == 0 {
+            None
+        } else {
+            let n = self.psnr_frames as f64;
+            let [y, u, v] = self.psnr_total;
+            Some([y / n, u / n, v / n])
+        }
+    }
+
+    /// The mean SSIM, if it was computed for any frames.
+    pub fn ssim(&self) -> Option<f64> {
+        if self.ssim_frames == 0 {
+            None
+        } else {
+            Some(self.ssim_total / self.ssim_frames as f64)
+        }
+    }
+
+    pub(crate) fn record(&mut self, picture: &Picture) {
+        if let Some(psnr) = picture.psnr() {
+            self.psnr_frames += 1;
+            for (total, psnr) in self.psnr_total.iter_mut().zip(psnr.iter()) {
+                *total += psnr;
+            }
+        }
+        if let Some(ssim) = picture.ssim() {
+            self.ssim_frames += 1;
+            self.ssim_total += ssim;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runs_are_counted_in_decode_order() {
+        use FrameType::*;
+
+        // Displayed as I B B P B B P.
+        let mut history = FrameTypeHistory::new();
+        for &frame_type in &[IDR, P, B, B, P, B, B] {
+            history.push(frame_type);
+        }
+        assert_eq!(history.runs()[2], 1);
+
+        history.finish();
+        assert_eq!(history.runs()[0], 0);
+        assert_eq!(history.runs()[2], 2);
+        assert_eq!(history.longest_run(), Some(2));
+        assert_eq!(history.average_run(), Some(2.0));
+    }
+}